@@ -1,15 +1,16 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use omalley_aoc2022::{INPUTS, NAMES};
+use omalley_aoc2021::input_for_day;
 
 macro_rules! benchmarks {
     ($day:ident) => {
 
         fn benchmark_function(c: &mut Criterion) {
-            use omalley_aoc2022::$day;
-            let posn = NAMES.iter().position(|n| *n == stringify!($day)).expect("Unknown day");
-            let input = $day::generator(INPUTS[posn]);
+            use omalley_aoc2021::$day;
+            let day = stringify!($day).trim_start_matches("day").parse().expect("Unknown day");
+            let text = input_for_day(day).expect("Can't find input");
+            let input = $day::generator(text);
             c.bench_function(concat!(stringify!($day), " gen"), |b| {
-                b.iter(|| $day::generator(INPUTS[posn]))
+                b.iter(|| $day::generator(text))
             });
             c.bench_function(concat!(stringify!($day), " part 1"), |b| {
                 b.iter(|| $day::part1(&input))
@@ -23,5 +24,64 @@ macro_rules! benchmarks {
 
 benchmarks!(day19);
 
-criterion_group!(benches, benchmark_function);
+/// Build a bingo game with many boards, each holding 25 distinct numbers
+/// from a simple pseudo-random shuffle.
+fn big_bingo(boards: usize) -> String {
+    let mut seed: u64 = 12345;
+    let mut next = move || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+    let mut numbers: Vec<usize> = (0..100).collect();
+    let moves: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+    let mut result = moves.join(",");
+    for _ in 0..boards {
+        for i in (1..numbers.len()).rev() {
+            numbers.swap(i, next() % (i + 1));
+        }
+        result.push('\n');
+        for row in numbers[..25].chunks(5) {
+            let row: Vec<String> = row.iter().map(|n| n.to_string()).collect();
+            result.push('\n');
+            result.push_str(&row.join(" "));
+        }
+    }
+    result
+}
+
+fn day4_marking(c: &mut Criterion) {
+    use omalley_aoc2021::day4;
+    let input = day4::generator(&big_bingo(1000));
+    c.bench_function("day4 indexed", |b| b.iter(|| day4::part2(&input)));
+    c.bench_function("day4 scan", |b| b.iter(|| day4::last_win_score_by_scan(&input)));
+}
+
+/// Tile the day 11 input into a grid that is copies wide and high.
+fn big_octopus(copies: usize) -> String {
+    let input = input_for_day(11).expect("Can't find input");
+    let mut result = String::new();
+    for _ in 0..copies {
+        for line in input.lines().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            result.push_str(&line.repeat(copies));
+            result.push('\n');
+        }
+    }
+    result
+}
+
+fn day11_flashes(c: &mut Criterion) {
+    use omalley_aoc2021::day11;
+    let input = day11::generator(&big_octopus(30));
+    c.bench_function("day11 flashed", |b| b.iter(|| day11::flashes_after(&input, 10)));
+    c.bench_function("day11 scan", |b| b.iter(|| day11::flashes_after_by_scan(&input, 10)));
+}
+
+fn day12_counting(c: &mut Criterion) {
+    use omalley_aoc2021::day12;
+    let input = day12::generator(input_for_day(12).expect("Can't find input"));
+    c.bench_function("day12 memoized", |b| b.iter(|| day12::count_paths(&input, true)));
+    c.bench_function("day12 listed", |b| b.iter(|| day12::paths(&input, true).len()));
+}
+
+criterion_group!(benches, benchmark_function, day4_marking, day11_flashes, day12_counting);
 criterion_main!(benches);
@@ -0,0 +1,30 @@
+use std::fs;
+use std::path::Path;
+
+/// Copy the input for each src/day?.rs into OUT_DIR so that lib.rs can
+/// include it. A day without an input file gets an empty input and a
+/// warning rather than a compile error, so the code can be written before
+/// the input has been downloaded.
+fn main() {
+    println!("cargo:rerun-if-changed=input");
+    println!("cargo:rerun-if-changed=src");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let out_input = Path::new(&out_dir).join("input");
+    fs::create_dir_all(&out_input).expect("Can't create input directory");
+    for entry in fs::read_dir("src").expect("Can't read src") {
+        let file_name = entry.expect("Can't read src").file_name();
+        let file_name = file_name.to_string_lossy();
+        let day = match file_name.strip_suffix(".rs") {
+            Some(day) if day.starts_with("day") => day,
+            _ => continue,
+        };
+        let input = Path::new("input").join(format!("{}.txt", day));
+        let dest = out_input.join(format!("{}.txt", day));
+        if input.exists() {
+            fs::copy(&input, &dest).expect("Can't copy input");
+        } else {
+            println!("cargo:warning=Missing {}, using an empty input", input.display());
+            fs::write(&dest, "").expect("Can't write empty input");
+        }
+    }
+}
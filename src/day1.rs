@@ -1,36 +1,133 @@
-fn count_descents(nums: &Vec<i64>) -> usize {
-  let mut count = 0;
-  let mut last = i64::MAX;
-  for current in nums {
-    if *current > last {
-      count += 1;
-    }
-    last = *current;
-  }
-  count
+use std::collections::VecDeque;
+
+/// Count how many times the sum over a sliding window of the given size
+/// is larger than the previous window's sum. Since consecutive windows
+/// share all but their end values, that only requires comparing the value
+/// leaving the window with the one entering it.
+pub fn increases(nums: &[i64], window: usize) -> usize {
+  nums.windows(window + 1)
+    .filter(|w| w[window] > w[0])
+    .count()
 }
 
-fn count_triple_descents(nums: &Vec<i64>) -> usize {
+/// Count the increases like `increases`, but over a stream of depths so
+/// that only the last `window` values are kept in memory. It produces the
+/// same answer as the vector based `increases`.
+pub fn count_increases_streaming(lines: impl Iterator<Item = i64>, window: usize) -> usize {
+  if window == 0 {
+    return 0;
+  }
+  let mut recent: VecDeque<i64> = VecDeque::with_capacity(window);
   let mut count = 0;
-  let mut last = i64::MAX;
-  for i in 0..nums.len()-2 {
-    let current = nums[i] + nums[i+1] + nums[i+2];
-    if current > last {
+  for depth in lines {
+    if recent.len() == window && depth > recent.pop_front().unwrap() {
       count += 1;
     }
-    last = current;
+    recent.push_back(depth);
   }
   count
 }
 
+/// Find the positions of the measurements that are larger than the one
+/// before them.
+pub fn increase_indices(nums: &[i64]) -> Vec<usize> {
+  nums.windows(2).enumerate()
+    .filter(|(_, w)| w[1] > w[0])
+    .map(|(i, _)| i + 1)
+    .collect()
+}
+
+/// Parse the depths one per line, skipping blank lines. Returns an error
+/// naming the first line that isn't a number.
+pub fn parse_depths(data: &str) -> Result<Vec<i64>, String> {
+  data.lines().enumerate()
+    .map(|(i, x)| (i, x.trim()))
+    .filter(|(_, x)| !x.is_empty())
+    .map(|(i, x)| x.parse::<i64>()
+      .map_err(|e| format!("Bad depth on line {}: {:?} ({})", i + 1, x, e)))
+    .collect()
+}
+
 pub fn generator(data: &str) -> Vec<i64> {
-  data.lines().map(|x| x.trim().parse::<i64>().unwrap()).collect()
+  parse_depths(data).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn part1(input: &[i64]) -> usize {
+  increases(input, 1)
 }
 
-pub fn part1(input: &Vec<i64>) -> usize {
-  count_descents(input)
+pub fn part2(input: &[i64]) -> usize {
+  increases(input, 3)
 }
 
-pub fn part2(input: &Vec<i64>) -> usize {
-  count_triple_descents(input)
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+  use crate::day1::{count_increases_streaming, generator, increase_indices, increases,
+                    parse_depths, part1};
+
+  /// The original loop based implementation to compare against.
+  fn count_window_increases(nums: &[i64], window: usize) -> usize {
+    if window == 0 || nums.len() < window {
+      return 0;
+    }
+    let mut count = 0;
+    let mut last = i64::MAX;
+    for start in 0..=nums.len() - window {
+      let current: i64 = nums[start..start + window].iter().sum();
+      if current > last {
+        count += 1;
+      }
+      last = current;
+    }
+    count
+  }
+
+  #[test]
+  fn test_short_input() {
+    assert_eq!(0, increases(&[], 3));
+    assert_eq!(0, increases(&[1], 3));
+    assert_eq!(0, increases(&[1, 2], 3));
+    assert_eq!(0, increases(&[1, 2, 3], 3));
+    assert_eq!(1, increases(&[1, 2, 3, 4], 3));
+  }
+
+  #[test]
+  fn test_window_sizes() {
+    let nums = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+    assert_eq!(7, increases(&nums, 1));
+    assert_eq!(5, increases(&nums, 3));
+    assert_eq!(0, increases(&nums, 11));
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(Ok(vec![1, 2, 3]), parse_depths("1\n  2  \n\n3\n\n"));
+    assert_eq!(Err(String::from("Bad depth on line 2: \"x12\" (invalid digit found in string)")),
+               parse_depths("1\nx12\n3"));
+  }
+
+  #[test]
+  fn test_increase_indices() {
+    let nums = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+    assert_eq!(vec![1, 2, 3, 5, 6, 7, 9], increase_indices(&nums));
+    let nums = generator(crate::input_for_day(1).expect("Can't find input"));
+    assert_eq!(part1(&nums), increase_indices(&nums).len());
+  }
+
+  #[test]
+  fn test_matches_loop() {
+    let nums = generator(crate::input_for_day(1).expect("Can't find input"));
+    for window in 0..=5 {
+      assert_eq!(count_window_increases(&nums, window), increases(&nums, window));
+    }
+  }
+
+  #[test]
+  fn test_streaming() {
+    let nums = generator(crate::input_for_day(1).expect("Can't find input"));
+    for window in 0..=5 {
+      assert_eq!(increases(&nums, window),
+                 count_increases_streaming(nums.iter().copied(), window));
+    }
+  }
+}
@@ -1,101 +1,386 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt;
+
 #[derive(Debug)]
 pub enum ParseResult {
   OK,
-  Corrupted{_expect: char, found: char},
+  Corrupted{expected: char, found: char},
   Incomplete{expect: Vec<char>},
   Illegal(char),
-  Underflow,
+  /// a closing character without a matching open
+  Underflow(char),
 }
 
-fn score(close: char) -> u64 {
-  match close {
-    ')' => 3,
-    ']' => 57,
-    '}' => 1197,
-    '>' => 25137,
-    _ => 0,
+impl fmt::Display for ParseResult {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ParseResult::OK => write!(f, "ok"),
+      ParseResult::Corrupted{expected, found} =>
+        write!(f, "corrupted: expected {}, found {}", expected, found),
+      ParseResult::Incomplete{expect} =>
+        write!(f, "incomplete, need {}", expect.iter().collect::<String>()),
+      ParseResult::Illegal(ch) => write!(f, "illegal character {}", ch),
+      ParseResult::Underflow(ch) => write!(f, "underflow: {} has no opening bracket", ch),
+    }
   }
 }
 
-fn fix_score(close: &Vec<char>) -> u64 {
-  close.iter()
-    .map(|c|
-      match c {
-        ')' => 1,
-        ']' => 2,
-        '}' => 3,
-        '>' => 4,
-        _ => 0})
-     .fold(0, |a, b| 5 * a + b)
+/// A kind of bracket and the scores for it.
+#[derive(Clone, Debug)]
+pub struct BracketPair {
+  pub open: char,
+  pub close: char,
+  /// the score when this is the wrong closing character
+  pub error_score: u64,
+  /// the score for each of these that completes a line
+  pub completion_score: u64,
 }
 
-fn closer(start: char) -> Option<char> {
-  match start {
-    '[' => Some(']'),
-    '(' => Some(')'),
-    '<' => Some('>'),
-    '{' => Some('}'),
-    _ => None,
-  }
+/// The set of brackets that the parser understands.
+#[derive(Clone, Debug)]
+pub struct Brackets {
+  pairs: Vec<BracketPair>,
+  /// the completion score is multiplied by this before each character
+  multiplier: u64,
 }
 
-fn is_close(close: char) -> bool {
-  match close {
-    ']' | ')' | '>' | '}' => true,
-    _ => false,
+impl Default for Brackets {
+  /// The brackets and scores from the puzzle.
+  fn default() -> Self {
+    Brackets::new(vec![
+      BracketPair{open: '(', close: ')', error_score: 3, completion_score: 1},
+      BracketPair{open: '[', close: ']', error_score: 57, completion_score: 2},
+      BracketPair{open: '{', close: '}', error_score: 1197, completion_score: 3},
+      BracketPair{open: '<', close: '>', error_score: 25137, completion_score: 4},
+    ], 5).expect("Invalid default brackets")
   }
 }
 
-fn parse(input: &str) -> ParseResult {
-  let mut stack: Vec<char> = Vec::new();
-  for ch in input.chars() {
-    if is_close(ch) {
-      let top = stack.pop();
-      match top {
-        None => return ParseResult::Underflow,
-        Some(req) => if req != ch {
-          return ParseResult::Corrupted{_expect: req, found: ch}
+impl Brackets {
+  /// Build a set of brackets, which must each have a different opening
+  /// and closing character so that every closer has exactly one opener.
+  pub fn new(pairs: Vec<BracketPair>, multiplier: u64) -> Result<Self, String> {
+    let mut seen = HashSet::new();
+    for pair in &pairs {
+      for ch in [pair.open, pair.close] {
+        if !seen.insert(ch) {
+          return Err(format!("{:?} is used by more than one bracket", ch));
+        }
+      }
+    }
+    Ok(Brackets{pairs, multiplier})
+  }
+
+  fn closer(&self, start: char) -> Option<char> {
+    self.pairs.iter().find(|p| p.open == start).map(|p| p.close)
+  }
+
+  fn is_close(&self, close: char) -> bool {
+    self.pairs.iter().any(|p| p.close == close)
+  }
+
+  fn score(&self, close: char) -> u64 {
+    self.pairs.iter().find(|p| p.close == close).map_or(0, |p| p.error_score)
+  }
+
+  fn fix_score(&self, close: &[char]) -> u64 {
+    close.iter()
+      .map(|&c| self.pairs.iter().find(|p| p.close == c).map_or(0, |p| p.completion_score))
+      .fold(0, |a, b| self.multiplier * a + b)
+  }
+
+  /// Parse a line with these brackets.
+  pub fn parse(&self, input: &str) -> ParseResult {
+    let mut stack: Vec<char> = Vec::new();
+    for ch in input.chars() {
+      if self.is_close(ch) {
+        let top = stack.pop();
+        match top {
+          None => return ParseResult::Underflow(ch),
+          Some(req) => if req != ch {
+            return ParseResult::Corrupted{expected: req, found: ch}
+          }
+        }
+      } else {
+        let close = self.closer(ch);
+        match close {
+          None => return ParseResult::Illegal(ch),
+          Some(goal) => stack.push(goal),
         }
       }
+    }
+    if stack.is_empty() {
+      ParseResult::OK
     } else {
-      let close = closer(ch);
-      match close {
-        None => return ParseResult::Illegal(ch),
-        Some(goal) => stack.push(goal),
+      stack.reverse();
+      ParseResult::Incomplete{expect: stack}
+    }
+  }
+
+  /// Parse each non-blank line.
+  pub fn parse_lines(&self, data: &str) -> Vec<ParseResult> {
+    data.lines()
+      .map(|x| x.trim())
+      .filter(|x| !x.is_empty())
+      .map(|x| self.parse(x))
+      .collect()
+  }
+
+  /// Total the scores for the first illegal character on corrupted lines.
+  pub fn error_score(&self, input: &[ParseResult]) -> u64 {
+    input.iter()
+      .map(|r| match r {
+        ParseResult::Corrupted{found: ch, ..} => self.score(*ch),
+        _ => 0 })
+      .sum()
+  }
+
+  /// Find the middle score for completing the incomplete lines, which
+  /// is 0 if there are no incomplete lines.
+  pub fn completion_score(&self, input: &[ParseResult]) -> u64 {
+    let mut fix: Vec<u64> = input.iter()
+      .map(|r| match r {
+        ParseResult::Incomplete{expect: e} => self.fix_score(e),
+        _ => 0 })
+      .filter(|x| *x > 0)
+      .collect();
+    fix.sort();
+    fix.get(fix.len() / 2).copied().unwrap_or(0)
+  }
+
+  /// Compute the error and completion scores without keeping the parse
+  /// results. Only the completion scores are kept to find their median.
+  pub fn scores_streaming<'a>(&self, lines: impl Iterator<Item=&'a str>) -> (u64, u64) {
+    let mut errors = 0;
+    let mut completions = RunningMedian::default();
+    for line in lines.map(|x| x.trim()).filter(|x| !x.is_empty()) {
+      match self.parse(line) {
+        ParseResult::Corrupted{found, ..} => errors += self.score(found),
+        ParseResult::Incomplete{expect} => {
+          let score = self.fix_score(&expect);
+          if score > 0 {
+            completions.add(score);
+          }
+        }
+        _ => {}
       }
     }
+    (errors, completions.median().unwrap_or(0))
+  }
+}
+
+/// Get the characters that would complete an incomplete line.
+pub fn completion(result: &ParseResult) -> Option<String> {
+  match result {
+    ParseResult::Incomplete{expect} => Some(expect.iter().collect()),
+    _ => None,
   }
-  if stack.len() == 0 {
-    ParseResult::OK
+}
+
+/// Check that every line is made of matching brackets, which the puzzle
+/// input always is, and describe each line that isn't.
+pub fn validate(results: &[ParseResult]) -> Result<(), String> {
+  let errors: Vec<String> = results.iter().enumerate()
+    .filter_map(|(i, r)| match r {
+      ParseResult::Illegal(ch) =>
+        Some(format!("Line {} has an illegal character {:?}", i + 1, ch)),
+      ParseResult::Underflow(ch) =>
+        Some(format!("Line {} has {:?} without an opening bracket", i + 1, ch)),
+      _ => None,
+    })
+    .collect();
+  if errors.is_empty() {
+    Ok(())
   } else {
-    stack.reverse();
-    ParseResult::Incomplete{expect: stack}
+    Err(errors.join("; "))
+  }
+}
+
+/// Keeps the median of a stream of numbers by splitting them into a
+/// lower and upper half. For an even count, the median is the smaller
+/// number in the upper half, which matches part2.
+#[derive(Default)]
+struct RunningMedian {
+  lower: BinaryHeap<u64>,
+  upper: BinaryHeap<Reverse<u64>>,
+}
+
+impl RunningMedian {
+  fn add(&mut self, value: u64) {
+    match self.upper.peek() {
+      Some(Reverse(smallest)) if value < *smallest => self.lower.push(value),
+      _ => self.upper.push(Reverse(value)),
+    }
+    // keep the upper half the same size or one larger than the lower half
+    if self.upper.len() > self.lower.len() + 1 {
+      let Reverse(value) = self.upper.pop().unwrap();
+      self.lower.push(value);
+    } else if self.lower.len() > self.upper.len() {
+      let value = self.lower.pop().unwrap();
+      self.upper.push(Reverse(value));
+    }
+  }
+
+  fn median(&self) -> Option<u64> {
+    self.upper.peek().map(|Reverse(value)| *value)
   }
 }
 
+/// Compute both parts with the puzzle's brackets without keeping the
+/// parse results.
+pub fn scores_streaming<'a>(lines: impl Iterator<Item=&'a str>) -> (u64, u64) {
+  Brackets::default().scores_streaming(lines)
+}
+
+/// Count the lines that are OK, corrupted, and incomplete.
+pub fn summary(results: &[ParseResult]) -> (usize, usize, usize) {
+  results.iter().fold((0, 0, 0), |(ok, corrupted, incomplete), r| match r {
+    ParseResult::OK => (ok + 1, corrupted, incomplete),
+    ParseResult::Corrupted{..} => (ok, corrupted + 1, incomplete),
+    ParseResult::Incomplete{..} => (ok, corrupted, incomplete + 1),
+    _ => (ok, corrupted, incomplete),
+  })
+}
+
 pub fn generator(data: &str) -> Vec<ParseResult> {
-  data.lines()
-    .map(|x| x.trim())
-    .filter(|x| x.len() > 0)
-    .map(|x| parse(&x))
-    .collect()
-}
-
-pub fn part1(input: &Vec<ParseResult>) -> u64 {
-  input.iter()
-    .map(|r| match r {
-      ParseResult::Corrupted{_expect: _, found: ch} => score(*ch),
-      _ => 0 })
-    .sum()
-}
-
-pub fn part2(input: &Vec<ParseResult>) -> u64 {
-  let mut fix: Vec<u64> = input.iter()
-    .map(|r| match r {
-      ParseResult::Incomplete{expect: e} => fix_score(e),
-      _ => 0 })
-    .filter(|x| *x > 0)
-    .collect();
-  fix.sort();
-  fix[fix.len() /2]
+  Brackets::default().parse_lines(data)
+}
+
+pub fn part1(input: &[ParseResult]) -> u64 {
+  Brackets::default().error_score(input)
+}
+
+pub fn part2(input: &[ParseResult]) -> u64 {
+  Brackets::default().completion_score(input)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::day10::{BracketPair, Brackets, completion, generator, ParseResult, part1, part2,
+                    RunningMedian, scores_streaming, summary, validate};
+
+  fn parse(line: &str) -> ParseResult {
+    Brackets::default().parse(line)
+  }
+
+  const INPUT: &str = "[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]
+";
+
+  #[test]
+  fn test_parts() {
+    let results = generator(INPUT);
+    assert_eq!(26397, part1(&results));
+    assert_eq!(288957, part2(&results));
+  }
+
+  #[test]
+  fn test_custom_brackets() {
+    let mut pairs = Brackets::default().pairs;
+    pairs.push(BracketPair{open: '«', close: '»', error_score: 100000, completion_score: 5});
+    let brackets = Brackets::new(pairs, 6).unwrap();
+    let results = brackets.parse_lines("(«[]»)\n(«]\n«<\n(«»\n");
+    assert!(matches!(results[0], ParseResult::OK));
+    assert_eq!(57, brackets.error_score(&results));
+    // "»" is 6 * 4 + 5 = 29 and ")" is 1
+    assert_eq!(Some(String::from(">»")), completion(&results[2]));
+    assert_eq!(vec![29, 1],
+               results[2..].iter().map(|r| match r {
+                 ParseResult::Incomplete{expect} => brackets.fix_score(expect),
+                 _ => 0}).collect::<Vec<u64>>());
+    assert_eq!(29, brackets.completion_score(&results));
+    assert_eq!((57, 29), brackets.scores_streaming("(«[]»)\n(«]\n«<\n(«»\n".lines()));
+    // the default brackets don't know about «»
+    assert!(matches!(parse("(«»)"), ParseResult::Illegal('«')));
+  }
+
+  #[test]
+  fn test_summary() {
+    let results = generator(INPUT);
+    assert_eq!((0, 5, 5), summary(&results));
+    let results = generator(crate::input_for_day(10).expect("Can't find input"));
+    let (ok, corrupted, incomplete) = summary(&results);
+    assert_eq!(results.len(), ok + corrupted + incomplete);
+    assert_eq!((1, 1, 1), summary(&generator("()\n(]\n(\n")));
+  }
+
+  #[test]
+  fn test_streaming() {
+    assert_eq!((26397, 288957), scores_streaming(INPUT.lines()));
+    let input = crate::input_for_day(10).expect("Can't find input");
+    let results = generator(input);
+    assert_eq!((part1(&results), part2(&results)), scores_streaming(input.lines()));
+    assert_eq!((0, 0), scores_streaming("".lines()));
+  }
+
+  #[test]
+  fn test_running_median() {
+    let mut median = RunningMedian::default();
+    assert_eq!(None, median.median());
+    let values = [5, 1, 9, 3, 3, 8, 2, 7];
+    for (i, value) in values.iter().enumerate() {
+      median.add(*value);
+      let mut sorted = values[..=i].to_vec();
+      sorted.sort();
+      assert_eq!(Some(sorted[sorted.len() / 2]), median.median());
+    }
+  }
+
+  #[test]
+  fn test_no_incomplete() {
+    let results = generator("{([(<{}[<>[]}>{[]{[(<()>\n[[<[([]))<([[{}[[()]]]\n");
+    assert_eq!(0, part2(&results));
+    assert_eq!(1197 + 3, part1(&results));
+    assert_eq!(0, part2(&generator("()\n[]\n")));
+    assert_eq!(0, part2(&[]));
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!("corrupted: expected ], found }",
+               parse("{([(<{}[<>[]}>{[]{[(<()>").to_string());
+    assert_eq!("incomplete, need }}]])})]", parse("[({(<(())[]>[[{[]{<()<>>").to_string());
+    assert_eq!("ok", parse("([])").to_string());
+    assert_eq!("illegal character x", parse("(x)").to_string());
+    assert_eq!("underflow: ] has no opening bracket", parse("()]").to_string());
+  }
+
+  #[test]
+  fn test_broken_brackets() {
+    let pair = |open, close| BracketPair{open, close, error_score: 1, completion_score: 1};
+    assert_eq!(Some(String::from("')' is used by more than one bracket")),
+               Brackets::new(vec![pair('(', ')'), pair('[', ')')], 5).err());
+    assert_eq!(Some(String::from("'(' is used by more than one bracket")),
+               Brackets::new(vec![pair('(', ')'), pair('(', ']')], 5).err());
+    assert_eq!(Some(String::from("'|' is used by more than one bracket")),
+               Brackets::new(vec![pair('|', '|')], 5).err());
+    assert_eq!(Some(String::from("')' is used by more than one bracket")),
+               Brackets::new(vec![pair('(', ')'), pair(')', '(')], 5).err());
+  }
+
+  #[test]
+  fn test_validate() {
+    assert_eq!(Ok(()), validate(&generator(INPUT)));
+    assert_eq!(Err(String::from("Line 2 has an illegal character 'x'; \
+                                 Line 3 has ')' without an opening bracket")),
+               validate(&generator("(<>)\n(x)\n())\n[[\n")));
+  }
+
+  #[test]
+  fn test_completion() {
+    assert_eq!(Some(String::from("}}]])})]")), completion(&parse("[({(<(())[]>[[{[]{<()<>>")));
+    assert_eq!(None, completion(&parse("{([(<{}[<>[]}>{[]{[(<()>")));
+    assert_eq!(None, completion(&parse("()")));
+    let completions: Vec<String> = generator(INPUT).iter().filter_map(completion).collect();
+    assert_eq!(vec!["}}]])})]", ")}>]})", "}}>}>))))", "]]}}]}]}>", "])}>"], completions);
+  }
 }
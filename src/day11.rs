@@ -1,3 +1,6 @@
+use colored::Colorize;
+use std::fmt;
+
 #[derive(Clone,Copy,Debug)]
 struct Point {
   x: usize,
@@ -6,27 +9,51 @@ struct Point {
 
 const OCTOPUS_RADIX: u32 = 10;
 
-#[derive(Clone,Debug,Default)]
+#[derive(Clone,Debug)]
 pub struct Octopus {
   energy: Vec<Vec<u32>>,
   width: usize,
   turn: u64,
+  /// how far (in Chebyshev distance) a flash reaches
+  radius: usize,
+}
+
+impl Default for Octopus {
+  fn default() -> Self {
+    Octopus{energy: Vec::new(), width: 0, turn: 0, radius: 1}
+  }
 }
 
 impl Octopus {
-  fn parse(input: &mut dyn Iterator<Item = &str>) -> Self {
-    let energy: Vec<Vec<u32>> = input.map(|line| line.chars()
-      .map(|c| c.to_digit(OCTOPUS_RADIX).unwrap())
+  /// Parse the grid of energy levels. Unless lenient is set, every row
+  /// must have the same width. Lenient parsing ignores the extra cells
+  /// in the longer rows.
+  fn parse(input: &mut dyn Iterator<Item = &str>, lenient: bool) -> Result<Self, String> {
+    let energy: Vec<Vec<u32>> = input.enumerate().map(|(row, line)| line.chars()
+      .map(|c| c.to_digit(OCTOPUS_RADIX)
+        .ok_or_else(|| format!("Row {} has an invalid energy {:?}", row + 1, c)))
       .collect())
-      .collect();
-    let width = energy.iter().map(|x| x.len()).min().unwrap();
-    Octopus{energy, width, turn: 0}
+      .collect::<Result<_, _>>()?;
+    let width = energy.iter().map(|x| x.len()).min().ok_or("The grid is empty")?;
+    if !lenient {
+      let expected = energy[0].len();
+      if let Some((row, line)) = energy.iter().enumerate().find(|(_, x)| x.len() != expected) {
+        return Err(format!("Row {} has width {} instead of {}", row + 1, line.len(), expected));
+      }
+    }
+    Ok(Octopus{energy, width, turn: 0, radius: 1})
+  }
+
+  /// Use a different flash radius, where 1 is the adjacent cells.
+  pub fn with_radius(self, radius: usize) -> Self {
+    Octopus{radius, ..self}
   }
 
   fn neighbors(&self, pnt: &Point) -> Vec<Point> {
     let mut result: Vec<Point> = Vec::new();
-    for relative_x in -1..=1 {
-      for relative_y in -1..=1 {
+    let radius = self.radius as i64;
+    for relative_x in -radius..=radius {
+      for relative_y in -radius..=radius {
         if relative_x != 0 || relative_y != 0 {
           let off_x = pnt.x as i64 + relative_x;
           let off_y = pnt.y as i64 + relative_y;
@@ -40,17 +67,56 @@ impl Octopus {
     result
   }
   
+  /// Add one to every cell and spread the flashes. Only the cells that
+  /// flash are revisited, both to bump their neighbors and to reset them.
   fn advance(&mut self) -> u64 {
+    let mut to_do: Vec<Point> = Vec::new();
+    for (y, row) in self.energy.iter_mut().enumerate() {
+      for (x, level) in row[..self.width].iter_mut().enumerate() {
+        *level += 1;
+        if *level == OCTOPUS_RADIX {
+          to_do.push(Point{x, y});
+        }
+      }
+    }
+
+    // bump the neighbors of each flash, which may cause more flashes
+    let height = self.energy.len();
+    let mut flashed: Vec<Point> = Vec::new();
+    while let Some(p) = to_do.pop() {
+      flashed.push(p);
+      for y in p.y.saturating_sub(self.radius)..(p.y + self.radius + 1).min(height) {
+        for x in p.x.saturating_sub(self.radius)..(p.x + self.radius + 1).min(self.width) {
+          if x != p.x || y != p.y {
+            self.energy[y][x] += 1;
+            if self.energy[y][x] == OCTOPUS_RADIX {
+              to_do.push(Point{x, y});
+            }
+          }
+        }
+      }
+    }
+
+    self.turn += 1;
+    for p in &flashed {
+      self.energy[p.y][p.x] = 0;
+    }
+    flashed.len() as u64
+  }
+
+  /// Advance by pushing every cell through the work list and then
+  /// rescanning the whole grid to reset the flashed cells. This is the
+  /// original approach, which is kept for the tests and benchmarks.
+  fn advance_by_scan(&mut self) -> u64 {
     let mut to_do: Vec<Point> = Vec::new();
     for x in 0..self.width {
       for y in 0..self.energy.len() {
-        to_do.push(Point{x : x, y: y});
+        to_do.push(Point{x, y});
       }
     }
 
     // update all of the squares
-    while to_do.len() > 0 {
-      let p = to_do.pop().unwrap();
+    while let Some(p) = to_do.pop() {
       self.energy[p.y][p.x] += 1;
       // if it went to 10, bump up the neighbors again
       if self.energy[p.y][p.x] == OCTOPUS_RADIX {
@@ -59,7 +125,7 @@ impl Octopus {
     }
 
     self.turn += 1;
-    
+
     let mut lights = 0;
     for x in 0..self.width {
       for y in 0..self.energy.len() {
@@ -73,28 +139,256 @@ impl Octopus {
   }
 }
 
-pub fn generator(data: &str) -> Octopus {
+impl Octopus {
+  /// Draw the energy levels like the puzzle does. If highlight is set,
+  /// the cells that flashed on the last step are in bold.
+  fn render(&self, highlight: bool) -> String {
+    let mut result = String::new();
+    for row in &self.energy {
+      for &level in &row[..self.width] {
+        if highlight && level == 0 && self.turn > 0 {
+          result.push_str(&"0".bold().to_string());
+        } else {
+          result.push_str(&level.to_string());
+        }
+      }
+      result.push('\n');
+    }
+    result
+  }
+}
+
+impl fmt::Display for Octopus {
+  /// Print the energy levels with the flashed cells in bold.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.render(true))
+  }
+}
+
+pub fn parse_octopus(data: &str, lenient: bool) -> Result<Octopus, String> {
   Octopus::parse(&mut data.lines()
     .map(|x| x.trim())
-    .filter(|x| x.len() > 0))
+    .filter(|x| !x.is_empty()), lenient)
 }
 
-pub fn part1(input: &Octopus) -> u64 {
-  let mut octo = (*input).clone();
-  let mut flashes = 0;
-  for _ in 0..100 {
-    flashes += octo.advance();
+pub fn generator(data: &str) -> Octopus {
+  parse_octopus(data, false).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Count the flashes over the given number of steps.
+pub fn flashes_after(octo: &Octopus, steps: u64) -> u64 {
+  let mut octo = octo.clone();
+  (0..steps).map(|_| octo.advance()).sum()
+}
+
+/// Count the flashes by rescanning the grid after each step.
+pub fn flashes_after_by_scan(octo: &Octopus, steps: u64) -> u64 {
+  let mut octo = octo.clone();
+  (0..steps).map(|_| octo.advance_by_scan()).sum()
+}
+
+/// The number of flashes on each of the given number of steps.
+pub fn flashes_per_step(octo: &Octopus, steps: u64) -> Vec<u64> {
+  let mut octo = octo.clone();
+  (0..steps).map(|_| octo.advance()).collect()
+}
+
+/// A copy of the energy levels after the given number of steps.
+pub fn state_after(octo: &Octopus, steps: u64) -> Vec<Vec<u32>> {
+  let mut octo = octo.clone();
+  for _ in 0..steps {
+    octo.advance();
   }
-  flashes
+  octo.energy.iter().map(|row| row[..octo.width].to_vec()).collect()
 }
 
-pub fn part2(input: &Octopus) -> u64 {
-  let mut octo = (*input).clone();
+pub fn part1(input: &Octopus) -> u64 {
+  flashes_after(input, 100)
+}
+
+/// Render the grid after each of the given number of steps, with the
+/// flashed cells in bold if highlight is set.
+pub fn renderings(input: &Octopus, steps: u64, highlight: bool) -> Vec<String> {
+  let mut octo = input.clone();
+  (0..steps).map(|_| {
+    octo.advance();
+    octo.render(highlight)
+  }).collect()
+}
+
+/// The most steps that first_sync_flash will try.
+pub const SYNC_LIMIT: u64 = 1_000_000;
+
+/// Find the first turn where every octopus flashes at once. Returns an
+/// error if that doesn't happen within SYNC_LIMIT steps.
+pub fn first_sync_flash(octo: &Octopus) -> Result<u64, String> {
+  let mut octo = octo.clone();
   let octopus_count = (octo.width * octo.energy.len()) as u64;
-  while octo.advance() != octopus_count {
-    // pass
+  for _ in 0..SYNC_LIMIT {
+    if octo.advance() == octopus_count {
+      return Ok(octo.turn)
+    }
   }
-  octo.turn
+  Err(format!("The octopuses didn't all flash together within {} steps", SYNC_LIMIT))
+}
+
+pub fn part2(input: &Octopus) -> u64 {
+  first_sync_flash(input).unwrap_or_else(|e| panic!("{}", e))
 }
 
+#[cfg(test)]
+mod tests {
+  use colored::Colorize;
+  use crate::day11::{first_sync_flash, flashes_after, flashes_after_by_scan, flashes_per_step,
+                     generator, Octopus, parse_octopus, part1, part2, renderings, state_after};
+
+  /// Advance a copy of the octopuses by the given number of steps.
+  fn octo_after(octo: &Octopus, steps: u64) -> Octopus {
+    let mut octo = octo.clone();
+    for _ in 0..steps {
+      octo.advance();
+    }
+    octo
+  }
+
+  const INPUT: &str = "5483143223
+2745854711
+5264556173
+6141336146
+6357385478
+4167524645
+2176841721
+6882881134
+4846848554
+5283751526
+";
 
+  #[test]
+  fn test_parts() {
+    let octo = generator(INPUT);
+    assert_eq!(1656, part1(&octo));
+    assert_eq!(195, part2(&octo));
+  }
+
+  #[test]
+  fn test_flashes_after() {
+    let octo = generator(INPUT);
+    assert_eq!(0, flashes_after(&octo, 0));
+    assert_eq!(204, flashes_after(&octo, 10));
+    assert_eq!(part1(&octo), flashes_after(&octo, 100));
+  }
+
+  #[test]
+  fn test_by_scan() {
+    let octo = generator(INPUT);
+    assert_eq!(204, flashes_after_by_scan(&octo, 10));
+    assert_eq!(part1(&octo), flashes_after_by_scan(&octo, 100));
+    let octo = generator(crate::input_for_day(11).expect("Can't find input"));
+    for steps in [1, 10, 100, 300] {
+      assert_eq!(flashes_after_by_scan(&octo, steps), flashes_after(&octo, steps));
+    }
+  }
+
+  #[test]
+  fn test_flashes_per_step() {
+    let octo = generator(INPUT);
+    let counts = flashes_per_step(&octo, 200);
+    assert_eq!(200, counts.len());
+    assert_eq!(&[0, 35], &counts[..2]);
+    assert_eq!(part1(&octo), counts[..100].iter().sum::<u64>());
+    // the first step where all 100 octopuses flash
+    let first_sync = counts.iter().position(|&c| c == 100).unwrap() as u64 + 1;
+    assert_eq!(part2(&octo), first_sync);
+  }
+
+  #[test]
+  fn test_state_after() {
+    let octo = generator(INPUT);
+    assert_eq!(octo.energy, state_after(&octo, 0));
+    let state = state_after(&octo, 2);
+    assert_eq!(vec![8, 8, 0, 7, 4, 7, 6, 5, 5, 5], state[0]);
+    for steps in [1, 10, 100, 195] {
+      let state = state_after(&octo, steps);
+      assert_eq!(10, state.len());
+      assert!(state.iter().all(|row| row.len() == 10 && row.iter().all(|x| (0..=9).contains(x))));
+    }
+    // every octopus just flashed together
+    assert!(state_after(&octo, 195).iter().flatten().all(|&x| x == 0));
+  }
+
+  #[test]
+  fn test_ragged() {
+    assert_eq!(Some(String::from("Row 3 has width 4 instead of 5")),
+               parse_octopus("12345\n12345\n1234\n12345\n", false).err());
+    assert_eq!(Some(String::from("Row 2 has width 6 instead of 5")),
+               parse_octopus("12345\n123456\n", false).err());
+    assert_eq!(Some(String::from("Row 2 has an invalid energy 'x'")),
+               parse_octopus("123\n1x3\n", false).err());
+    assert_eq!(Some(String::from("The grid is empty")), parse_octopus("\n", false).err());
+    // the lenient parse ignores the extra cells
+    let octo = parse_octopus("99\n990\n", true).unwrap();
+    assert_eq!(vec![vec![0, 0], vec![0, 0]], state_after(&octo, 1));
+  }
+
+  #[test]
+  fn test_first_sync_flash() {
+    assert_eq!(Ok(195), first_sync_flash(&generator(INPUT)));
+    assert_eq!(Ok(1), first_sync_flash(&generator("99\n99\n")));
+    // without any spreading, cells with different energy never line up
+    let octo = generator("05\n").with_radius(0);
+    assert_eq!(Some(String::from("The octopuses didn't all flash together within 1000000 steps")),
+               first_sync_flash(&octo).err());
+  }
+
+  #[test]
+  fn test_radius() {
+    let octo = generator(INPUT);
+    assert_eq!(1656, part1(&octo.clone().with_radius(1)));
+    let mut row = generator("90000\n");
+    let mut wide_row = row.clone().with_radius(2);
+    row.advance();
+    assert_eq!(vec![vec![0, 2, 1, 1, 1]], row.energy);
+    wide_row.advance();
+    assert_eq!(vec![vec![0, 2, 2, 1, 1]], wide_row.energy);
+    // with a radius of 2, a flash in one corner reaches the opposite corner
+    let grid = generator("900\n000\n008\n");
+    assert_eq!(1, flashes_after(&grid, 1));
+    assert_eq!(2, flashes_after(&grid.clone().with_radius(2), 1));
+  }
+
+  #[test]
+  fn test_renderings() {
+    let octo = generator(INPUT);
+    assert_eq!(INPUT, octo.to_string());
+    let steps = renderings(&octo, 2, false);
+    assert_eq!(2, steps.len());
+    assert_eq!("6594254334
+3856965822
+6375667284
+7252447257
+7468496589
+5278635756
+3287952832
+7993992245
+5957959665
+6394862637
+", steps[0]);
+    assert_eq!("8807476555
+5089087054
+8597889608
+8485769600
+8700908800
+6600088989
+6800005943
+0000007456
+9000000876
+8700006848
+", steps[1]);
+    // the flashed cells are highlighted
+    let bold = "0".bold().to_string();
+    let highlighted = renderings(&octo, 2, true);
+    assert_eq!(steps[1].replace('0', &bold), highlighted[1]);
+    assert!(highlighted[1].starts_with(&format!("88{}7", bold)));
+    assert_eq!(highlighted[1], octo_after(&octo, 2).to_string());
+  }
+}
@@ -1,10 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 
-#[derive(Default,Debug)]
+#[derive(Default)]
 pub struct CaveSystem {
   caves: HashMap<String,Cave>,
 }
 
+// Print the caves in sorted order so the output is the same on each run.
+impl fmt::Debug for CaveSystem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("CaveSystem")
+      .field("caves", &self.caves.iter().collect::<BTreeMap<_, _>>())
+      .finish()
+  }
+}
+
 #[derive(Default,Debug)]
 pub struct Cave {
   passages: Vec<String>,
@@ -42,34 +52,78 @@ impl CaveSystem {
   }
 }
 
+/// How often a path may revisit small caves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VisitBudget {
+  /// the most times that any one small cave may be visited
+  pub max_visits: usize,
+  /// how many different small caves may be visited more than once
+  pub repeated_caves: usize,
+}
+
+impl VisitBudget {
+  /// Each small cave is visited at most once (part 1).
+  pub const SINGLE: VisitBudget = VisitBudget{max_visits: 1, repeated_caves: 0};
+  /// A single small cave may be visited twice (part 2).
+  pub const ONE_DOUBLE: VisitBudget = VisitBudget{max_visits: 2, repeated_caves: 1};
+
+  pub fn new(allow_double: bool) -> Self {
+    if allow_double { VisitBudget::ONE_DOUBLE } else { VisitBudget::SINGLE }
+  }
+
+  /// Can a small cave that has already been visited the given number of
+  /// times be visited again, when repeats caves have already been
+  /// revisited? If so, returns the new count of revisited caves.
+  fn visit(&self, visits: usize, repeats: usize) -> Option<usize> {
+    if visits == 0 {
+      Some(repeats)
+    } else if visits >= self.max_visits {
+      None
+    } else if visits > 1 {
+      Some(repeats)
+    } else if repeats < self.repeated_caves {
+      Some(repeats + 1)
+    } else {
+      None
+    }
+  }
+}
+
+impl Default for VisitBudget {
+  fn default() -> Self {
+    VisitBudget::SINGLE
+  }
+}
+
 #[derive(Debug)]
 struct Decision {
   name: String,
   next: usize,
-  used_double: bool,
+  /// the number of small caves that have been visited more than once
+  repeats: usize,
 }
 
 impl Decision {
-  fn new(name: &str, used_double: bool) -> Self {
-    Decision{name: String::from(name), next: 0, used_double: used_double}
+  fn new(name: &str, repeats: usize) -> Self {
+    Decision{name: String::from(name), next: 0, repeats}
   }
 }
 
 #[derive(Debug)]
 struct PathState<'a> {
   caves: &'a CaveSystem,
+  budget: VisitBudget,
   path: Vec<Decision>,
 }
 
 impl<'a> PathState<'a> {
-  fn new(caves: &'a CaveSystem, allow_double: bool) -> Self {
-    PathState{path: vec![Decision::new(CaveSystem::START, !allow_double)],
-              caves: caves.clone()}
+  fn new(caves: &'a CaveSystem, budget: VisitBudget) -> Self {
+    PathState{path: vec![Decision::new(CaveSystem::START, 0)], budget, caves}
   }
 
-  // Is this a second visit to a small cave?
-  fn is_double_visit(&self, next: &str, is_big: bool) -> bool {
-    !is_big && self.path.iter().any(|x| x.name == next)
+  // How many times has this path visited a small cave?
+  fn visits(&self, next: &str, is_big: bool) -> usize {
+    if is_big { 0 } else { self.path.iter().filter(|x| x.name == next).count() }
   }
 }
 
@@ -77,26 +131,25 @@ impl<'a> Iterator for PathState<'a> {
   type Item = Vec<String>;
   
   fn next(&mut self) -> Option<Self::Item> {
-    while self.path.len() > 0 {
+    while !self.path.is_empty() {
       let last_entry: usize = self.path.len() - 1;
-      let mut current = &mut self.path[last_entry];
-      let used_double = current.used_double;
+      let current = &mut self.path[last_entry];
+      let repeats = current.repeats;
       let current_cave = &self.caves.caves[&current.name];
       if current.next >= current_cave.passages.len() {
         self.path.pop();
       } else {
         let next = &current_cave.passages[current.next];
         current.next += 1;
-        let next_is_double = self.is_double_visit(next,
-          self.caves.caves[next].is_big);
+        let visits = self.visits(next, self.caves.caves[next].is_big);
 
-        if !used_double || !next_is_double {
-          self.path.push(Decision::new(next, used_double || next_is_double));
-        }
-        if next == CaveSystem::END {
-          return Some(self.path.iter()
-                          .map(|x| x.name.clone())
-                          .collect())
+        if let Some(repeats) = self.budget.visit(visits, repeats) {
+          self.path.push(Decision::new(next, repeats));
+          if next == CaveSystem::END {
+            return Some(self.path.iter()
+                            .map(|x| x.name.clone())
+                            .collect())
+          }
         }
       }
     }
@@ -104,19 +157,304 @@ impl<'a> Iterator for PathState<'a> {
   }
 }
 
+/// Counts the paths without listing them by remembering how many ways
+/// there are to finish from each state.
+struct PathCounter {
+  /// the passages out of each cave by index
+  passages: Vec<Vec<usize>>,
+  /// the position of each small cave in the visit counts
+  small: Vec<Option<usize>>,
+  end: usize,
+  budget: VisitBudget,
+  /// (cave, visits to each small cave, repeated caves) -> completions
+  memo: HashMap<(usize, Vec<u32>, usize), usize>,
+}
+
+impl PathCounter {
+  fn new(caves: &CaveSystem, budget: VisitBudget) -> Self {
+    let index: HashMap<&str, usize> = caves.caves.keys().enumerate()
+      .map(|(i, name)| (name.as_str(), i))
+      .collect();
+    let passages = caves.caves.values()
+      .map(|cave| cave.passages.iter().map(|x| index[x.as_str()]).collect())
+      .collect();
+    let mut next_small = 0;
+    let small = caves.caves.values()
+      .map(|cave| if cave.is_big { None } else {
+        next_small += 1;
+        Some(next_small - 1)
+      })
+      .collect();
+    let end = index.get(CaveSystem::END).copied().unwrap_or(usize::MAX);
+    PathCounter{passages, small, end, budget, memo: HashMap::new()}
+  }
+
+  /// Count the ways to reach the end from the given cave, where visits
+  /// has the number of times that each small cave has been visited.
+  fn count(&mut self, cave: usize, visits: &mut Vec<u32>, repeats: usize) -> usize {
+    if cave == self.end {
+      return 1
+    }
+    if let Some(&result) = self.memo.get(&(cave, visits.clone(), repeats)) {
+      return result
+    }
+    let mut result = 0;
+    for i in 0..self.passages[cave].len() {
+      let next = self.passages[cave][i];
+      match self.small[next] {
+        None => result += self.count(next, visits, repeats),
+        Some(small) => {
+          if let Some(repeats) = self.budget.visit(visits[small] as usize, repeats) {
+            visits[small] += 1;
+            result += self.count(next, visits, repeats);
+            visits[small] -= 1;
+          }
+        }
+      }
+    }
+    self.memo.insert((cave, visits.clone(), repeats), result);
+    result
+  }
+}
+
+/// Count the paths from start to end that visit the given cave.
+pub fn paths_through(input: &CaveSystem, cave: &str, allow_double: bool) -> usize {
+  PathState::new(input, VisitBudget::new(allow_double))
+    .filter(|path| path.iter().any(|x| x == cave))
+    .count()
+}
+
+/// Count the paths from start to end using memoization, which is much
+/// faster than listing them.
+pub fn count_paths(input: &CaveSystem, allow_double: bool) -> usize {
+  count_paths_with_budget(input, VisitBudget::new(allow_double))
+}
+
+/// Count the paths from start to end that stay within the budget.
+pub fn count_paths_with_budget(input: &CaveSystem, budget: VisitBudget) -> usize {
+  let start = match input.caves.keys().position(|x| x == CaveSystem::START) {
+    Some(start) => start,
+    None => return 0,
+  };
+  let mut counter = PathCounter::new(input, budget);
+  let mut visits = vec![0; counter.small.iter().flatten().count()];
+  if let Some(small) = counter.small[start] {
+    visits[small] = 1;
+  }
+  counter.count(start, &mut visits, 0)
+}
+
+/// Is there any route from start to end? The shortest route never
+/// revisits a cave, so this only needs to check reachability.
+pub fn has_path(input: &CaveSystem) -> bool {
+  let mut seen: BTreeSet<&str> = BTreeSet::new();
+  let mut to_do: Vec<&str> = vec![CaveSystem::START];
+  while let Some(name) = to_do.pop() {
+    let cave = match input.caves.get(name) {
+      Some(cave) => cave,
+      None => continue,
+    };
+    if cave.is_end {
+      return true
+    }
+    if seen.insert(name) {
+      to_do.extend(cave.passages.iter().map(|x| x.as_str()));
+    }
+  }
+  false
+}
+
 pub fn generator(data: &str) -> CaveSystem {
   CaveSystem::parse(&mut data.lines()
     .map(|x| x.trim())
     .filter(|x| x.len() > 0))
 }
 
+/// List every path from start to end, where allow_double lets a single
+/// small cave be visited twice.
+pub fn paths(input: &CaveSystem, allow_double: bool) -> Vec<Vec<String>> {
+  paths_with_budget(input, VisitBudget::new(allow_double))
+}
+
+/// List every path from start to end that stays within the budget.
+pub fn paths_with_budget(input: &CaveSystem, budget: VisitBudget) -> Vec<Vec<String>> {
+  PathState::new(input, budget).collect()
+}
+
+/// The number of paths that visit small caves at most once. Zero is a
+/// valid answer when there is no route to the end (see has_path).
 pub fn part1(input: &CaveSystem) -> usize {
-  let result = PathState::new(input, false);
-  result.count()
+  count_paths(input, false)
 }
 
+/// The number of paths that may visit a single small cave twice. Like
+/// part1, zero means that there is no route to the end.
 pub fn part2(input: &CaveSystem) -> usize {
-  let result = PathState::new(input, true);
-  result.count()
+  count_paths(input, true)
 }
 
+#[cfg(test)]
+mod tests {
+  use crate::day12::{count_paths, count_paths_with_budget, generator, has_path, part1, part2,
+                     paths, paths_through, paths_with_budget, PathState, VisitBudget};
+
+  const SMALL: &str = "start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end
+";
+
+  const MEDIUM: &str = "dc-end
+HN-start
+start-kj
+dc-start
+dc-HN
+LN-dc
+HN-end
+kj-sa
+kj-HN
+kj-dc
+";
+
+  #[test]
+  fn test_parts() {
+    let caves = generator(SMALL);
+    assert_eq!(10, part1(&caves));
+    assert_eq!(36, part2(&caves));
+    let caves = generator(MEDIUM);
+    assert_eq!(19, part1(&caves));
+    assert_eq!(103, part2(&caves));
+  }
+
+  #[test]
+  fn test_count_paths() {
+    let large = "fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\n\
+                 RW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW\n";
+    assert_eq!(226, count_paths(&generator(large), false));
+    assert_eq!(3509, count_paths(&generator(large), true));
+    for input in [SMALL, MEDIUM, large, crate::input_for_day(12).expect("Can't find input")] {
+      let caves = generator(input);
+      for allow_double in [false, true] {
+        assert_eq!(paths(&caves, allow_double).len(), count_paths(&caves, allow_double));
+      }
+    }
+    assert_eq!(0, count_paths(&generator("a-b\n"), true));
+  }
+
+  #[test]
+  fn test_budget() {
+    let small = generator(SMALL);
+    let medium = generator(MEDIUM);
+    assert_eq!(part1(&small), paths_with_budget(&small, VisitBudget::default()).len());
+    assert_eq!(part2(&small), paths_with_budget(&small, VisitBudget::new(true)).len());
+    let two_doubles = VisitBudget{max_visits: 2, repeated_caves: 2};
+    assert_eq!(54, paths_with_budget(&small, two_doubles).len());
+    assert_eq!(247, paths_with_budget(&medium, two_doubles).len());
+    let triple = VisitBudget{max_visits: 3, repeated_caves: 1};
+    assert_eq!(83, paths_with_budget(&small, triple).len());
+    assert_eq!(305, paths_with_budget(&medium, triple).len());
+    // revisiting more caves than there are changes nothing
+    let many = VisitBudget{max_visits: 1, repeated_caves: 5};
+    assert_eq!(10, paths_with_budget(&small, many).len());
+    // the memoized counts match the listed paths
+    for caves in [&small, &medium] {
+      for budget in [VisitBudget::SINGLE, VisitBudget::ONE_DOUBLE, two_doubles, triple, many] {
+        assert_eq!(paths_with_budget(caves, budget).len(), count_paths_with_budget(caves, budget));
+      }
+    }
+    assert_eq!(54, count_paths_with_budget(&small, two_doubles));
+    assert_eq!(305, count_paths_with_budget(&medium, triple));
+  }
+
+  #[test]
+  fn test_debug_order() {
+    let text = format!("{:?}", generator("start-zz\nzz-end\nstart-B\nB-end\n"));
+    let positions: Vec<usize> = ["\"B\"", "\"end\"", "\"start\"", "\"zz\""].iter()
+      .map(|name| text.find(name).unwrap())
+      .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", text);
+  }
+
+  #[test]
+  fn test_many_small_caves() {
+    // a chain of 70 small caves joined by big caves
+    let mut input = String::from("start-c0\n");
+    for i in 0..69 {
+      input.push_str(&format!("c{}-B{}\nB{}-c{}\n", i, i, i, i + 1));
+    }
+    input.push_str("c69-end\n");
+    let caves = generator(&input);
+    assert_eq!(1, part1(&caves));
+    assert_eq!(139, part2(&caves));
+  }
+
+  #[test]
+  fn test_has_path() {
+    assert!(has_path(&generator(SMALL)));
+    assert!(has_path(&generator(MEDIUM)));
+    assert!(has_path(&generator("start-end\n")));
+    // two separate islands
+    let caves = generator("start-A\nA-b\nc-end\nc-D\n");
+    assert!(!has_path(&caves));
+    assert_eq!(0, part1(&caves));
+    assert_eq!(0, part2(&caves));
+    assert!(paths(&caves, true).is_empty());
+    // without a start cave, there is nowhere to begin
+    assert!(!has_path(&generator("a-end\n")));
+  }
+
+  #[test]
+  fn test_path_state() {
+    let caves = generator(SMALL);
+    let state = PathState::new(&caves, VisitBudget::SINGLE);
+    assert!(std::ptr::eq(&caves, state.caves));
+    let listed: Vec<Vec<String>> = state.collect();
+    assert_eq!(paths(&caves, false), listed);
+    // several iterators can share the same cave system
+    let first = PathState::new(&caves, VisitBudget::ONE_DOUBLE);
+    let second = PathState::new(&caves, VisitBudget::ONE_DOUBLE);
+    assert!(first.zip(second).all(|(a, b)| a == b));
+    assert_eq!(36, PathState::new(&caves, VisitBudget::ONE_DOUBLE).count());
+  }
+
+  #[test]
+  fn test_paths_through() {
+    let caves = generator(SMALL);
+    assert_eq!(5, paths_through(&caves, "c", false));
+    assert_eq!(8, paths_through(&caves, "b", false));
+    assert_eq!(0, paths_through(&caves, "d", false));
+    assert_eq!(part1(&caves), paths_through(&caves, "start", false));
+    assert_eq!(part2(&caves), paths_through(&caves, "end", true));
+    assert_eq!(0, paths_through(&caves, "zz", true));
+    // d is a dead end, so reaching it uses up the double visit to b
+    assert_eq!(8, paths_through(&caves, "d", true));
+    assert!(paths(&caves, true).iter()
+      .filter(|x| x.iter().any(|c| c == "d"))
+      .all(|x| x.iter().filter(|c| *c == "b").count() == 2));
+  }
+
+  #[test]
+  fn test_paths() {
+    let caves = generator(SMALL);
+    let mut found: Vec<String> = paths(&caves, false).iter().map(|x| x.join(",")).collect();
+    found.sort();
+    let mut expected = vec!["start,A,b,A,c,A,end",
+                            "start,A,b,A,end",
+                            "start,A,b,end",
+                            "start,A,c,A,b,A,end",
+                            "start,A,c,A,b,end",
+                            "start,A,c,A,end",
+                            "start,A,end",
+                            "start,b,A,c,A,end",
+                            "start,b,A,end",
+                            "start,b,end"];
+    expected.sort();
+    assert_eq!(expected, found);
+    let doubled = paths(&caves, true);
+    assert_eq!(part2(&caves), doubled.len());
+    assert!(doubled.iter().all(|x| x.first().unwrap() == "start" && x.last().unwrap() == "end"));
+  }
+}
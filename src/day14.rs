@@ -1,12 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Default)]
 pub struct Problem {
   initial: String,
   insertions: HashMap<String, Vec<String>>,
   current: HashMap<String, u64>,
 }
 
+// List the insertion rules and pair counts alphabetically by pair.
+impl fmt::Debug for Problem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Problem")
+      .field("initial", &self.initial)
+      .field("insertions", &self.insertions.iter().collect::<BTreeMap<_, _>>())
+      .field("current", &self.current.iter().collect::<BTreeMap<_, _>>())
+      .finish()
+  }
+}
+
 impl Problem {
   fn parse_template(template: &str) -> HashMap<String, u64> {
     let mut result = HashMap::new();
@@ -91,3 +103,18 @@ pub fn part2(input: &Problem) -> u64 {
   }
   problem.score()
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::day14::generator;
+
+  #[test]
+  fn test_debug_order() {
+    let problem = generator("NNCB\n\nCH -> B\nHH -> N\nCB -> H\nNH -> C\n");
+    assert_eq!("Problem { initial: \"NNCB\", \
+                insertions: {\"CB\": [\"CH\", \"HB\"], \"CH\": [\"CB\", \"BH\"], \
+                \"HH\": [\"HN\", \"NH\"], \"NH\": [\"NC\", \"CH\"]}, \
+                current: {\"CB\": 1, \"NC\": 1, \"NN\": 1} }",
+               format!("{:?}", problem));
+  }
+}
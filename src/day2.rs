@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[derive(Clone,Debug)]
 pub enum Move {
   Up(i32),
   Down(i32),
@@ -5,15 +8,40 @@ pub enum Move {
 }
 
 impl Move {
-  fn parse(s: &str) -> Move {
+  /// Parse a command, which must have a non-negative distance.
+  pub fn parse(s: &str) -> Result<Move, String> {
+    Self::parse_with(s, false)
+  }
+
+  /// Parse a command, allowing negative distances that move the
+  /// submarine in the opposite direction.
+  pub fn parse_signed(s: &str) -> Result<Move, String> {
+    Self::parse_with(s, true)
+  }
+
+  fn parse_with(s: &str, allow_negative: bool) -> Result<Move, String> {
     let mut parts = s.split_whitespace();
-    let command = parts.next().unwrap();
-    let dist = parts.next().unwrap().parse::<i32>().unwrap();
-    match command {
-      "forward" => Move::Forward(dist),
-      "up" => Move::Up(dist),
-      "down" => Move::Down(dist),
-      _ => panic!("Unknown command {}", command),
+    let command = parts.next().ok_or_else(|| format!("Missing command in {:?}", s))?;
+    let dist = parts.next().ok_or_else(|| format!("Missing distance in {:?}", s))?
+      .parse::<i32>().map_err(|e| format!("Bad distance in {:?}: {}", s, e))?;
+    if dist < 0 && !allow_negative {
+      return Err(format!("Negative distance in {:?}", s));
+    }
+    match command.to_lowercase().as_str() {
+      "forward" | "f" => Ok(Move::Forward(dist)),
+      "up" | "u" => Ok(Move::Up(dist)),
+      "down" | "d" => Ok(Move::Down(dist)),
+      _ => Err(format!("Unknown command in {:?}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Move {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Move::Up(dist) => write!(f, "up {}", dist),
+      Move::Down(dist) => write!(f, "down {}", dist),
+      Move::Forward(dist) => write!(f, "forward {}", dist),
     }
   }
 }
@@ -44,30 +72,113 @@ impl Position {
     }
   }
 
-  fn area(self: &Position) -> i32 {
-    self.x * self.y
+  fn coordinates(self: &Position) -> (i32, i32) {
+    (self.x, self.y)
   }
 }
 
 pub fn generator(data: &str) -> Vec<Move> {
   data.lines()
     .map(|x| Move::parse(x.trim()))
-    .collect()
+    .collect::<Result<Vec<Move>, String>>()
+    .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Get the final horizontal position and depth using the part 1 rules.
+pub fn final_position_part1(cmds: &[Move]) -> (i32, i32) {
+  let mut posn = Position{x: 0, y: 0, aim: 0};
+  for c in cmds {
+    posn.part1_update(c);
+  }
+  posn.coordinates()
 }
 
-pub fn part1(cmds: &Vec<Move>) -> i32 {
+/// Get the final horizontal position and depth using the part 2 rules.
+pub fn final_position_part2(cmds: &[Move]) -> (i32, i32) {
   let mut posn = Position{x: 0, y: 0, aim: 0};
   for c in cmds {
-    posn.part1_update(&c);
+    posn.part2_update(c);
   }
-  posn.area()
+  posn.coordinates()
 }
 
-pub fn part2(cmds: &Vec<Move>) -> i32 {
+/// Run the commands once and return the answers for both parts. The two
+/// interpretations coexist because the part 1 depth is the part 2 aim.
+pub fn run_both(cmds: &[Move]) -> (i32, i32) {
   let mut posn = Position{x: 0, y: 0, aim: 0};
   for c in cmds {
-    posn.part2_update(&c);
+    posn.part2_update(c);
   }
-  posn.area()
+  (posn.x * posn.aim, posn.x * posn.y)
+}
+
+pub fn part1(cmds: &[Move]) -> i32 {
+  let (x, y) = final_position_part1(cmds);
+  x * y
 }
 
+pub fn part2(cmds: &[Move]) -> i32 {
+  let (x, y) = final_position_part2(cmds);
+  x * y
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::day2::{final_position_part1, final_position_part2, generator, Move, part1,
+                    part2, run_both};
+
+  const INPUT: &str = "forward 5
+down 5
+forward 8
+up 3
+down 8
+forward 2
+";
+
+  #[test]
+  fn test_final_position() {
+    let cmds = generator(INPUT);
+    assert_eq!((15, 10), final_position_part1(&cmds));
+    assert_eq!((15, 60), final_position_part2(&cmds));
+  }
+
+  #[test]
+  fn test_run_both() {
+    let cmds = generator(INPUT);
+    assert_eq!((150, 900), run_both(&cmds));
+    let cmds = generator(crate::input_for_day(2).expect("Can't find input"));
+    assert_eq!((part1(&cmds), part2(&cmds)), run_both(&cmds));
+  }
+
+  #[test]
+  fn test_display() {
+    for line in INPUT.lines() {
+      assert_eq!(line, Move::parse(line).unwrap().to_string());
+    }
+  }
+
+  #[test]
+  fn test_parse_aliases() {
+    assert_eq!("forward 5", Move::parse("FORWARD 5").unwrap().to_string());
+    assert_eq!("down 2", Move::parse("d 2").unwrap().to_string());
+    assert_eq!("up 7", Move::parse("Up 7").unwrap().to_string());
+    assert_eq!("forward 1", Move::parse("F 1").unwrap().to_string());
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_eq!(Err(String::from("Unknown command in \"sideways 3\"")),
+               Move::parse("sideways 3").map(|_| ()));
+    assert_eq!(Err(String::from("Missing distance in \"up\"")),
+               Move::parse("up").map(|_| ()));
+    assert!(Move::parse("down x").is_err());
+    assert!(Move::parse("").is_err());
+  }
+
+  #[test]
+  fn test_negative_distance() {
+    assert_eq!(Err(String::from("Negative distance in \"up -1\"")),
+               Move::parse("up -1").map(|_| ()));
+    assert_eq!("up -1", Move::parse_signed("up -1").unwrap().to_string());
+  }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::io;
 use std::io::{Error, ErrorKind};
 use std::rc::Rc;
@@ -363,7 +363,7 @@ impl Display for BreadCrumb {
   }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Default)]
 struct SymbolicValue {
   // for each value, track the constraints that got us there
   values: HashMap<i64, BreadCrumb>,
@@ -383,6 +383,15 @@ impl SymbolicValue {
   }
 }
 
+// values() returns the keys in ascending order.
+impl Debug for SymbolicValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_map()
+      .entries(self.values().iter().map(|val| (val, &self.values[val])))
+      .finish()
+  }
+}
+
 impl Display for SymbolicValue {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     let result = self.values().iter()
@@ -627,8 +636,9 @@ pub fn part2(program: &Vec<Operation>) -> i64 {
 
 #[cfg(test)]
 mod tests {
+  use std::collections::HashMap;
   use crate::day24::{BreadCrumb, ConstrainedEnvironment, generator, Operand, Register,
-                     SimpleEnvironment, State, SymbolicState};
+                     SimpleEnvironment, State, SymbolicState, SymbolicValue};
 
   const INPUT: &str =
 &"inp w
@@ -656,10 +666,7 @@ mod w 2
 
   #[test]
   fn test_execution() {
-    let id = crate::NAMES.iter()
-      .position(|&x| x == "day24")
-      .expect("Can't find input");
-    let program = generator(crate::INPUTS[id]);
+    let program = generator(crate::input_for_day(24).expect("Can't find input"));
     let inputs = vec![3,9,9,9,9,6,9,8,7,9,9,4,2,9];
     let env = SimpleEnvironment{inputs};
     let mut state = State::default();
@@ -686,6 +693,16 @@ eql y 56
     assert_eq!([56, 6, 1, 0], state.register);
   }
 
+  #[test]
+  fn test_debug_order() {
+    let values: HashMap<i64, BreadCrumb> = [100, -5, 30, 7].iter()
+      .map(|&v| (v, BreadCrumb::init(0)))
+      .collect();
+    assert_eq!("{-5: BreadCrumb { crumbs: [] }, 7: BreadCrumb { crumbs: [] }, \
+                30: BreadCrumb { crumbs: [] }, 100: BreadCrumb { crumbs: [] }}",
+               format!("{:?}", SymbolicValue{values}));
+  }
+
   #[test]
   fn test_breadcrumbs() {
     let mut descr = BreadCrumb::init(14);
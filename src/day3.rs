@@ -1,54 +1,175 @@
-fn compute_width(inputs: &Vec<u64>) -> u32 {
-  let mask: u64 = inputs.iter().fold(0, |x, y| x | y);
-  64 - u64::leading_zeros(mask)
+use num_bigint::BigUint;
+
+use crate::Answer;
+
+/// The diagnostic report. Lines of up to 64 bits are packed into a u64,
+/// while wider lines fall back to a list of bits.
+pub enum Report {
+  Narrow{values: Vec<u64>, width: u32},
+  /// the bits of each line from most to least significant
+  Wide(Vec<Vec<bool>>),
 }
 
-// Compute the difference in the count of ones versus zeros at
-// the given mask position.
-fn compare_bits(inputs: &Vec<u64>, mask: u64) -> i32 {
-  let mut result = 0;
-  for val in inputs {
-    if val & mask == 0 {
-      result -= 1;
-    } else {
-      result += 1;
+/// The rule that each rating uses to pick which bit to keep.
+#[derive(Clone, Copy, Debug)]
+enum Criteria {
+  /// Keep the most common bit, which is 1 on a tie (oxygen generator).
+  MostCommon,
+  /// Keep the least common bit, which is 0 on a tie (CO2 scrubber).
+  LeastCommon,
+}
+
+impl Criteria {
+  /// Given the count of ones minus the count of zeros at a position,
+  /// should the numbers with a 1 at that position be kept?
+  fn keep_ones(&self, ones_minus_zeros: i32) -> bool {
+    match self {
+      Criteria::MostCommon => ones_minus_zeros >= 0,
+      Criteria::LeastCommon => ones_minus_zeros < 0,
     }
   }
-  result
 }
 
-// req_bit takes the difference in 1's versus 0's and returns the
-// required value for the bit.
-fn compute_rating<F>(inputs: &Vec<u64>, mask: u64, req_bit: F) -> u64
-    where F: Fn(i32) -> bool {
+// Count the zeros and ones at the given mask position.
+fn count_bits(inputs: &[u64], mask: u64) -> (usize, usize) {
+  let ones = inputs.iter().filter(|&val| val & mask != 0).count();
+  (inputs.len() - ones, ones)
+}
+
+// Compute the difference in the count of ones versus zeros at
+// the given mask position.
+fn compare_bits(inputs: &[u64], mask: u64) -> i32 {
+  let (zeros, ones) = count_bits(inputs, mask);
+  ones as i32 - zeros as i32
+}
 
-  // if we have no mask or inputs, something went wrong
-  assert!(mask != 0 && inputs.len() > 0);
+/// Count the zeros and ones in each column from the most to the least
+/// significant bit.
+pub fn column_counts(inputs: &[u64], width: u32) -> Vec<(usize, usize)> {
+  (0..width).rev()
+    .map(|bit| count_bits(inputs, 1 << bit))
+    .collect()
+}
+
+// Filter the inputs one position at a time using the criteria until
+// a single number is left or every position has been used, which
+// happens when lines are repeated.
+fn compute_rating(inputs: &[u64], mask: u64, criteria: Criteria) -> u64 {
+
+  // if we have no inputs, something went wrong
+  assert!(!inputs.is_empty());
+
+  // the remaining numbers are all the same
+  if mask == 0 {
+    return inputs[0]
+  }
 
   // determine whether we need a 0 or 1 for this pass
-  let required_bit = req_bit(compare_bits(&inputs, mask));
+  let required_bit = criteria.keep_ones(compare_bits(inputs, mask));
 
   // filter the numbers with the right value at the mask position
-  let sub_list: Vec<u64> =
+  let mut sub_list: Vec<u64> =
     inputs.iter().filter(|x| ((*x & mask) != 0) == required_bit)
-          .map(|x| *x).collect();
+          .copied().collect();
+
+  // if every number has the same bit (such as leading zeros), nothing
+  // matches the least common bit, so skip this position
+  if sub_list.is_empty() {
+    sub_list = inputs.to_vec();
+  }
 
   // if we have a single answer use it, otherwise continue
   if sub_list.len() == 1 {
     sub_list[0]
   } else {
-    compute_rating(&sub_list, mask >> 1, req_bit)
+    compute_rating(&sub_list, mask >> 1, criteria)
   }
 }
 
-pub fn generator(data: &str)-> Vec<u64> {
-  data.lines()
-    .map(|x| u64::from_str_radix(x.trim(), 2)
-      .unwrap()).collect()
+// Compute the difference in the count of ones versus zeros in the
+// given column of wide lines.
+fn wide_compare_bits<B: AsRef<[bool]>>(inputs: &[B], column: usize) -> i32 {
+  inputs.iter().map(|bits| if bits.as_ref()[column] { 1 } else { -1 }).sum()
+}
+
+// The same as compute_rating, but for wide lines.
+fn wide_compute_rating(inputs: &[Vec<bool>], criteria: Criteria) -> Vec<bool> {
+  assert!(!inputs.is_empty());
+  let mut remaining: Vec<&Vec<bool>> = inputs.iter().collect();
+  for column in 0..inputs[0].len() {
+    if remaining.len() == 1 {
+      break;
+    }
+    let required_bit = criteria.keep_ones(wide_compare_bits(&remaining, column));
+    let sub_list: Vec<&Vec<bool>> = remaining.iter()
+      .filter(|bits| bits[column] == required_bit)
+      .copied()
+      .collect();
+    // skip positions where every number has the same bit
+    if !sub_list.is_empty() {
+      remaining = sub_list;
+    }
+  }
+  remaining[0].clone()
 }
 
-pub fn part1(inputs: &Vec<u64>) -> u64 {
-  let width = compute_width(inputs);
+/// Convert bits from most to least significant into a number.
+fn bits_to_big(bits: &[bool]) -> BigUint {
+  let digits: Vec<u8> = bits.iter().map(|&bit| bit as u8).collect();
+  BigUint::from_radix_be(&digits, 2).unwrap_or_default()
+}
+
+/// Multiply two numbers given as bits from most to least significant.
+/// The product of wide lines may not fit in an integer answer, in which
+/// case it is returned as decimal text.
+fn bits_product(left: &[bool], right: &[bool]) -> Answer {
+  let product = bits_to_big(left) * bits_to_big(right);
+  match i128::try_from(&product) {
+    Ok(value) => Answer::Integer(value),
+    Err(_) => Answer::Text(product.to_string()),
+  }
+}
+
+/// Parse the report, which must have at least one line and all of the
+/// lines must have the same width.
+pub fn parse_report(data: &str) -> Result<Report, String> {
+  let lines: Vec<&str> = data.lines()
+    .map(|x| x.trim())
+    .filter(|x| !x.is_empty())
+    .collect();
+  if lines.is_empty() {
+    return Err(String::from("The report is empty"));
+  }
+  // use the text's width, since the leading bits may all be zero
+  let width = lines[0].len();
+  if let Some((i, line)) = lines.iter().enumerate().find(|(_, x)| x.len() != width) {
+    return Err(format!("Line {} has width {} instead of {}: {:?}",
+                       i + 1, line.len(), width, line));
+  }
+  for (i, line) in lines.iter().enumerate() {
+    if let Some(ch) = line.chars().find(|c| *c != '0' && *c != '1') {
+      return Err(format!("Line {} has a non-binary digit {:?}: {:?}", i + 1, ch, line));
+    }
+  }
+  let width = width as u32;
+  if width > u64::BITS {
+    Ok(Report::Wide(lines.iter()
+      .map(|x| x.chars().map(|c| c == '1').collect())
+      .collect()))
+  } else {
+    let values = lines.iter()
+      .map(|x| u64::from_str_radix(x, 2).unwrap())
+      .collect();
+    Ok(Report::Narrow{values, width})
+  }
+}
+
+pub fn generator(data: &str) -> Report {
+  parse_report(data).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Compute the gamma and epsilon rates from the most and least common bits.
+pub fn rates(inputs: &[u64], width: u32) -> (u64, u64) {
   let mut bit_mask: u64 = 1 << (width - 1);
   let mut gamma: u64 = 0;
   while bit_mask != 0 {
@@ -59,13 +180,234 @@ pub fn part1(inputs: &Vec<u64>) -> u64 {
     bit_mask >>= 1;
   }
   // invert gamma to get epsilon
-  let epsilon = (!gamma) & ((1 << width) - 1);
-  gamma * epsilon
+  let epsilon = (!gamma) & (u64::MAX >> (u64::BITS - width));
+  (gamma, epsilon)
+}
+
+/// Compute the gamma and epsilon rates for wide lines.
+pub fn wide_rates(inputs: &[Vec<bool>]) -> (Vec<bool>, Vec<bool>) {
+  let gamma: Vec<bool> = (0..inputs[0].len())
+    .map(|column| wide_compare_bits(inputs, column) > 0)
+    .collect();
+  let epsilon = gamma.iter().map(|bit| !bit).collect();
+  (gamma, epsilon)
 }
 
-pub fn part2(inputs: &Vec<u64>) -> u64 {
-  let mask = 1 << (compute_width(inputs) - 1);
-  let o2_rating = compute_rating(inputs, mask, |x| x >= 0);
-  let co2_rating = compute_rating(inputs, mask, |x| x < 0);
-  o2_rating * co2_rating
+/// Compute the oxygen generator and CO2 scrubber ratings.
+pub fn ratings(inputs: &[u64], width: u32) -> (u64, u64) {
+  let mask = 1 << (width - 1);
+  (compute_rating(inputs, mask, Criteria::MostCommon),
+   compute_rating(inputs, mask, Criteria::LeastCommon))
+}
+
+/// Compute the oxygen generator and CO2 scrubber ratings for wide lines.
+pub fn wide_ratings(inputs: &[Vec<bool>]) -> (Vec<bool>, Vec<bool>) {
+  (wide_compute_rating(inputs, Criteria::MostCommon),
+   wide_compute_rating(inputs, Criteria::LeastCommon))
+}
+
+/// Multiply two narrow values without overflowing. The product is text
+/// if it doesn't fit in an integer answer.
+fn narrow_product(left: u64, right: u64) -> Answer {
+  let product = left as u128 * right as u128;
+  match i128::try_from(product) {
+    Ok(value) => Answer::Integer(value),
+    Err(_) => Answer::Text(product.to_string()),
+  }
+}
+
+pub fn part1(report: &Report) -> Answer {
+  match report {
+    Report::Narrow{values, width} => {
+      let (gamma, epsilon) = rates(values, *width);
+      narrow_product(gamma, epsilon)
+    }
+    Report::Wide(lines) => {
+      let (gamma, epsilon) = wide_rates(lines);
+      bits_product(&gamma, &epsilon)
+    }
+  }
+}
+
+pub fn part2(report: &Report) -> Answer {
+  match report {
+    Report::Narrow{values, width} => {
+      let (o2_rating, co2_rating) = ratings(values, *width);
+      narrow_product(o2_rating, co2_rating)
+    }
+    Report::Wide(lines) => {
+      let (o2_rating, co2_rating) = wide_ratings(lines);
+      bits_product(&o2_rating, &co2_rating)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Answer;
+  use crate::day3::{bits_product, column_counts, compute_rating, Criteria, generator,
+                    parse_report, part1, part2, rates, ratings, Report, wide_rates, wide_ratings};
+
+  /// Get the packed values and width from a narrow report.
+  fn narrow(report: &Report) -> (&[u64], u32) {
+    match report {
+      Report::Narrow{values, width} => (values, *width),
+      Report::Wide(_) => panic!("Expected a narrow report"),
+    }
+  }
+
+  /// Parse a list of bits such as "0110".
+  fn bits(s: &str) -> Vec<bool> {
+    s.chars().map(|c| c == '1').collect()
+  }
+
+  const INPUT: &str = "00100
+11110
+10110
+10111
+10101
+01111
+00111
+11100
+10000
+11001
+00010
+01010
+";
+
+  #[test]
+  fn test_leading_zeros() {
+    let report = generator("00100\n00110\n00010\n");
+    assert_eq!(5, narrow(&report).1);
+    assert_eq!(Answer::Integer(6 * 25), part1(&report));
+    assert_eq!(Answer::Integer(6 * 2), part2(&report));
+  }
+
+  #[test]
+  fn test_components() {
+    let report = generator(INPUT);
+    let (values, width) = narrow(&report);
+    assert_eq!((22, 9), rates(values, width));
+    assert_eq!((23, 10), ratings(values, width));
+    assert_eq!(Answer::Integer(198), part1(&report));
+    assert_eq!(Answer::Integer(230), part2(&report));
+  }
+
+  #[test]
+  fn test_column_counts() {
+    let report = generator(INPUT);
+    let (values, width) = narrow(&report);
+    let counts = column_counts(values, width);
+    assert_eq!(vec![(5, 7), (7, 5), (4, 8), (5, 7), (7, 5)], counts);
+    assert!(counts.iter().all(|(zeros, ones)| zeros + ones == values.len()));
+  }
+
+  #[test]
+  fn test_wide() {
+    let padding = "0".repeat(65);
+    let input: String = INPUT.lines()
+      .map(|line| format!("{}{}\n", padding, line))
+      .collect();
+    let report = generator(&input);
+    let lines = match &report {
+      Report::Wide(lines) => lines,
+      Report::Narrow{..} => panic!("Expected a wide report"),
+    };
+    let (gamma, epsilon) = wide_rates(lines);
+    assert_eq!(bits(&format!("{}10110", padding)), gamma);
+    assert_eq!(bits(&format!("{}01001", "1".repeat(65))), epsilon);
+    let (o2_rating, co2_rating) = wide_ratings(lines);
+    assert_eq!(bits(&format!("{}10111", padding)), o2_rating);
+    assert_eq!(bits(&format!("{}01010", padding)), co2_rating);
+    assert_eq!(Answer::Integer(230), part2(&report));
+    // epsilon is 2^70 - 23, since the padding flips to ones
+    assert_eq!(Answer::Integer(22 * ((1 << 70) - 23)), part1(&report));
+  }
+
+  #[test]
+  fn test_wide_products() {
+    // gamma and epsilon are each 100 bits, so their product needs 150
+    let half = format!("{}{}", "1".repeat(50), "0".repeat(50));
+    let report = generator(&format!("{}\n{}\n{}\n", half, half, "0".repeat(100)));
+    assert_eq!(Answer::Text(String::from("1427247692705957345757085512991818042883178496")),
+               part1(&report));
+    // the CO2 scrubber rating is the line of zeros
+    assert_eq!(Answer::Integer(0), part2(&report));
+    let ones = bits(&"1".repeat(100));
+    assert_eq!(Answer::Text(String::from(
+                 "1606938044258990275541962092338627301321746534979799428890625")),
+               bits_product(&ones, &ones));
+    assert_eq!(Answer::Integer(0), bits_product(&[], &ones));
+  }
+
+  #[test]
+  fn test_full_width() {
+    let report = generator(&format!("{}\n{}\n", "1".repeat(64), "1".repeat(64)));
+    let (values, width) = narrow(&report);
+    assert_eq!((u64::MAX, 0), rates(values, width));
+  }
+
+  #[test]
+  fn test_narrow_products() {
+    // the rates are each 40 bits, so their product doesn't fit in a u64
+    let report = generator(&format!("{}\n{}\n{}\n", "10".repeat(20), "10".repeat(20),
+                                    "01".repeat(20)));
+    assert_eq!(Answer::Integer(268650182136095589211250), part1(&report));
+    // the ratings are each nearly 2^64, so their product doesn't fit in an i128
+    let report = generator(&format!("{}\n{}0\n", "1".repeat(64), "1".repeat(63)));
+    assert_eq!(Answer::Text(String::from("340282366920938463408034375210639556610")),
+               part2(&report));
+  }
+
+  #[test]
+  fn test_empty() {
+    assert_eq!(Some(String::from("The report is empty")), parse_report("\n\n").err());
+  }
+
+  #[test]
+  fn test_mixed_widths() {
+    assert_eq!(Some(String::from("Line 2 has width 3 instead of 5: \"101\"")),
+               parse_report("10110\n101\n10110\n").err());
+  }
+
+  #[test]
+  fn test_non_binary() {
+    assert_eq!(Some(String::from("Line 1 has a non-binary digit '2': \"10210\"")),
+               parse_report("10210\n").err());
+    assert_eq!(Some(String::from("Line 2 has a non-binary digit '+': \"+0110\"")),
+               parse_report("10110\n+0110\n").err());
+    let wide = format!("{}x\n", "0".repeat(70));
+    assert_eq!(Some(format!("Line 1 has a non-binary digit 'x': {:?}", wide.trim())),
+               parse_report(&wide).err());
+  }
+
+  #[test]
+  fn test_single_line() {
+    let report = generator("10110\n");
+    let (values, width) = narrow(&report);
+    assert_eq!((22, 22), ratings(values, width));
+    assert_eq!(Answer::Integer(22 * 22), part2(&report));
+  }
+
+  #[test]
+  fn test_duplicate_lines() {
+    let report = generator("10110\n10110\n");
+    let (values, width) = narrow(&report);
+    assert_eq!((22, 22), ratings(values, width));
+    assert_eq!(Answer::Integer(22 * 22), part2(&report));
+    let report = generator("10110\n10110\n01001\n");
+    assert_eq!(Answer::Integer(22 * 9), part2(&report));
+    // the rates are each 40 bits and the oxygen rating has a repeated line
+    let report = generator(&format!("{}\n{}\n{}\n", "10".repeat(20), "10".repeat(20),
+                                    "01".repeat(20)));
+    assert_eq!(Answer::Integer(268650182136095589211250), part2(&report));
+  }
+
+  #[test]
+  fn test_tie_breaking() {
+    // the first column is split evenly between ones and zeros
+    let inputs = [0b10, 0b01, 0b11, 0b00];
+    assert_eq!(0b11, compute_rating(&inputs, 0b10, Criteria::MostCommon));
+    assert_eq!(0b00, compute_rating(&inputs, 0b10, Criteria::LeastCommon));
+  }
 }
@@ -1,21 +1,51 @@
+use std::collections::HashMap;
+
 #[derive(Debug)]
 pub struct Board {
   numbers: Vec<Vec<i32>>,
+  /// the cells that hold each number
+  positions: HashMap<i32, Vec<(usize, usize)>>,
 }
 
 impl Board {
-  const BOARD_SIZE: usize = 5;
-
-  fn parse(input: &str) -> Self {
-    let numbers: Vec<Vec<i32>> = input.lines()
+  /// Parse a board, which may be any size as long as every row has
+  /// the same number of columns. The index is only used for errors.
+  fn parse(index: usize, lines: &[&str]) -> Result<Self, String> {
+    let numbers: Vec<Vec<i32>> = lines.iter()
       .map(|x| x.split_whitespace()
-        .map(|x| x.parse::<i32>().unwrap())
+        .map(|x| x.parse::<i32>()
+          .map_err(|_| format!("Board {} has an invalid number {:?}", index + 1, x)))
         .collect())
-      .collect();
-    assert_eq!(numbers.len(), Self::BOARD_SIZE);
-    assert_eq!(numbers.iter()
-      .map(|x| x.len()).sum::<usize>(), Self::BOARD_SIZE * Self::BOARD_SIZE);
-    Board{numbers}
+      .collect::<Result<_, _>>()?;
+    for (i, row) in numbers.iter().enumerate() {
+      if row.len() != numbers[0].len() {
+        return Err(format!("Board {} row {} has {} numbers instead of {}",
+                           index + 1, i + 1, row.len(), numbers[0].len()));
+      }
+    }
+    let mut positions: HashMap<i32, Vec<(usize, usize)>> = HashMap::new();
+    for (x, row) in numbers.iter().enumerate() {
+      for (y, &val) in row.iter().enumerate() {
+        positions.entry(val).or_default().push((x, y));
+      }
+    }
+    Ok(Board{numbers, positions})
+  }
+
+  fn rows(&self) -> usize {
+    self.numbers.len()
+  }
+
+  fn columns(&self) -> usize {
+    self.numbers[0].len()
+  }
+
+  /// Find the smallest number that appears in more than one cell.
+  fn duplicate(&self) -> Option<i32> {
+    self.positions.iter()
+      .filter(|(_, cells)| cells.len() > 1)
+      .map(|(&val, _)| val)
+      .min()
   }
 }
 
@@ -26,60 +56,113 @@ pub struct Bingo {
 }
 
 impl Bingo {
-  fn parse(input: &str) -> Self {
-    let mut sections = input.split("\n\n");
-    // Read the first section as a comma separated list of numbers.
-    let moves = sections.next().unwrap()
-      .split(",").map(|x| x.trim().parse::<i32>().unwrap())
-      .collect();
-    let boards = sections.map(|x| Board::parse(x)).collect();
-    Bingo{moves, boards}
+  /// Parse the game. The real puzzle never repeats a number on a board,
+  /// so duplicates are an error unless allow_duplicates is set, in which
+  /// case drawing a repeated number marks all of its cells.
+  fn parse(input: &str, allow_duplicates: bool) -> Result<Self, String> {
+    // Split into sections on runs of blank lines. Using lines() also
+    // drops the \r from Windows line endings.
+    let lines: Vec<&str> = input.lines().collect();
+    let mut sections = lines.split(|x| x.trim().is_empty())
+      .filter(|x| !x.is_empty());
+    // Read the first section as a comma separated list of numbers, which
+    // may be wrapped across several lines.
+    let moves = sections.next().ok_or("The game is empty")?.iter()
+      .map(|x| x.trim().trim_end_matches(','))
+      .collect::<Vec<_>>()
+      .join(",")
+      .split(',')
+      .map(|x| x.trim().parse::<i32>()
+        .map_err(|_| format!("Invalid move {:?}", x.trim())))
+      .collect::<Result<_, _>>()?;
+    let boards: Vec<Board> = sections.enumerate()
+      .map(|(i, lines)| Board::parse(i, lines))
+      .collect::<Result<_, _>>()?;
+    // all of the boards in a game must be the same size
+    if let Some(first) = boards.first() {
+      for (i, board) in boards.iter().enumerate() {
+        if board.rows() != first.rows() || board.columns() != first.columns() {
+          return Err(format!("Board {} is {}x{} instead of {}x{}", i + 1, board.rows(),
+                             board.columns(), first.rows(), first.columns()));
+        }
+      }
+    }
+    if !allow_duplicates {
+      for (i, board) in boards.iter().enumerate() {
+        if let Some(val) = board.duplicate() {
+          return Err(format!("Board {} has {} more than once", i + 1, val));
+        }
+      }
+    }
+    Ok(Bingo{moves, boards})
   }
 }
 
 #[derive(Debug)]
 struct MarkedBoard<'a> {
   board: &'a Board,
-  mark: [[bool; Board::BOARD_SIZE]; Board::BOARD_SIZE],
+  mark: Vec<Vec<bool>>,
+  /// the number of marked cells in each row and column
+  row_marks: Vec<usize>,
+  column_marks: Vec<usize>,
+  has_won: bool,
 }
 
 impl<'a> MarkedBoard<'a> {
   fn new(board: &'a Board) -> Self {
-    let mark = [[false; Board::BOARD_SIZE]; Board::BOARD_SIZE];
-    MarkedBoard{board, mark}
+    let mark = vec![vec![false; board.columns()]; board.rows()];
+    MarkedBoard{board, mark, row_marks: vec![0; board.rows()],
+      column_marks: vec![0; board.columns()], has_won: false}
   }
 
   fn won(&self) -> bool {
+    self.has_won
+  }
+
+  /// Check for a won board by looking at every cell.
+  fn scan_won(&self) -> bool {
     // look for winning rows
-    for x in 0..Board::BOARD_SIZE {
-      for y in 0..Board::BOARD_SIZE {
-        if !self.mark[x][y] {
-          break;
-        }
-        if y == Board::BOARD_SIZE - 1 {
-          return true
-        }
-      }
+    if self.mark.iter().any(|row| row.iter().all(|&m| m)) {
+      return true
     }
 
     // look for winning columns
-    for y in 0..Board::BOARD_SIZE {
-      for x in 0..Board::BOARD_SIZE {
-        if !self.mark[x][y] {
-          break;
-        }
-        if x == Board::BOARD_SIZE - 1 {
-          return true
+    (0..self.board.columns()).any(|y| self.mark.iter().all(|row| row[y]))
+  }
+
+  /// Has the board won if the two full diagonals also count? Only square
+  /// boards have diagonals.
+  fn won_with_diagonals(&self) -> bool {
+    if self.won() {
+      return true
+    }
+    let size = self.board.rows();
+    if size != self.board.columns() {
+      return false
+    }
+    (0..size).all(|i| self.mark[i][i]) ||
+      (0..size).all(|i| self.mark[i][size - 1 - i])
+  }
+
+  fn mark(&mut self, num: i32) {
+    for &(x, y) in self.board.positions.get(&num).into_iter().flatten() {
+      if !self.mark[x][y] {
+        self.mark[x][y] = true;
+        self.row_marks[x] += 1;
+        self.column_marks[y] += 1;
+        if self.row_marks[x] == self.board.columns() ||
+            self.column_marks[y] == self.board.rows() {
+          self.has_won = true;
         }
       }
     }
-    false
   }
 
-  fn mark(&mut self, num: i32) {
-    for x in 0..Board::BOARD_SIZE {
-      for y in 0..Board::BOARD_SIZE {
-        if self.board.numbers[x][y] == num {
+  /// Mark the number by looking at every cell.
+  fn scan_mark(&mut self, num: i32) {
+    for (x, row) in self.board.numbers.iter().enumerate() {
+      for (y, &val) in row.iter().enumerate() {
+        if val == num {
           self.mark[x][y] = true;
         }
       }
@@ -88,10 +171,10 @@ impl<'a> MarkedBoard<'a> {
 
   fn score(&self, num: i32) -> i64 {
     let mut sum : i64 = 0;
-    for x in 0..Board::BOARD_SIZE {
-      for y in 0..Board::BOARD_SIZE {
+    for (x, row) in self.board.numbers.iter().enumerate() {
+      for (y, &val) in row.iter().enumerate() {
         if !self.mark[x][y] {
-          sum += self.board.numbers[x][y] as i64;
+          sum += val as i64;
         }
       }
     }
@@ -99,37 +182,260 @@ impl<'a> MarkedBoard<'a> {
   }
 }
 
+/// Parse the game, optionally allowing boards with repeated numbers.
+pub fn parse_bingo(data: &str, allow_duplicates: bool) -> Result<Bingo, String> {
+  Bingo::parse(data, allow_duplicates)
+}
+
 pub fn generator(data: &str) -> Bingo {
-  Bingo::parse(data)
+  parse_bingo(data, false).unwrap_or_else(|e| panic!("{}", e))
 }
 
-pub fn part1(bingo: &Bingo) -> i64 {
+/// A winning board's index, the number that completed it, and its score.
+pub type Winner = (usize, i32, i64);
+
+// Play until the first board wins, optionally allowing the house rule
+// where a full diagonal also wins.
+fn play_first(bingo: &Bingo, diagonals: bool) -> Option<Winner> {
   let mut boards: Vec<MarkedBoard> =
-    bingo.boards.iter().map(|b| MarkedBoard::new(b))
+    bingo.boards.iter().map(MarkedBoard::new)
+      .collect();
+  for m in &bingo.moves {
+    for (i, b) in boards.iter_mut().enumerate() {
+      b.mark(*m);
+      let won = if diagonals { b.won_with_diagonals() } else { b.won() };
+      if won {
+        return Some((i, *m, b.score(*m)))
+      }
+    }
+  }
+  None
+}
+
+// Play every move and list the boards in the order that they win.
+// Boards that win on the same move are listed in board order.
+fn play_all(bingo: &Bingo) -> Vec<Winner> {
+  let mut boards: Vec<(usize, MarkedBoard)> =
+    bingo.boards.iter().map(MarkedBoard::new)
+      .enumerate()
       .collect();
+  let mut result = Vec::new();
   for m in &bingo.moves {
-    for b in &mut boards {
+    for (i, b) in &mut *boards {
       b.mark(*m);
       if b.won() {
-        return b.score(*m)
+        result.push((*i, *m, b.score(*m)));
       }
     }
+    boards.retain(|(_, x)| !x.won());
+    if boards.is_empty() {
+      break;
+    }
   }
-  0
+  result
 }
 
-pub fn part2(bingo: &Bingo) -> i64 {
+// Play until every board has won and return the last one.
+fn play_last(bingo: &Bingo) -> Option<Winner> {
+  let mut winners = play_all(bingo);
+  if winners.len() == bingo.boards.len() {
+    winners.pop()
+  } else {
+    None
+  }
+}
+
+/// List the boards in the order that they win along with their scores.
+/// Boards that never win are left out.
+pub fn win_order(bingo: &Bingo) -> Vec<(usize, i64)> {
+  play_all(bingo).into_iter()
+    .map(|(board, _, score)| (board, score))
+    .collect()
+}
+
+/// Find the score of the last board to win by rescanning every cell
+/// for each move. This is the original approach, which is kept as a
+/// reference for the tests and benchmarks.
+pub fn last_win_score_by_scan(bingo: &Bingo) -> i64 {
   let mut boards: Vec<MarkedBoard> =
-    bingo.boards.iter().map(|b| MarkedBoard::new(b))
+    bingo.boards.iter().map(MarkedBoard::new)
       .collect();
   for m in &bingo.moves {
     for b in &mut *boards {
-      b.mark(*m);
+      b.scan_mark(*m);
     }
-    if boards.len() == 1 && boards[0].won() {
+    if boards.len() == 1 && boards[0].scan_won() {
       return boards[0].score(*m)
     }
-    boards.retain(|x| !x.won());
+    boards.retain(|x| !x.scan_won());
   }
   0
 }
+
+/// Find the score of the first board to win, optionally allowing
+/// the house rule where a full diagonal also wins.
+pub fn first_win_score(bingo: &Bingo, diagonals: bool) -> i64 {
+  play_first(bingo, diagonals).map_or(0, |(_, _, score)| score)
+}
+
+/// Find the first board to win. Panics if no board wins.
+pub fn first_winner(bingo: &Bingo) -> Winner {
+  play_first(bingo, false).expect("No board won")
+}
+
+/// Find the last board to win. Panics if some board never wins.
+pub fn last_winner(bingo: &Bingo) -> Winner {
+  play_last(bingo).expect("Not every board won")
+}
+
+pub fn part1(bingo: &Bingo) -> i64 {
+  first_win_score(bingo, false)
+}
+
+pub fn part2(bingo: &Bingo) -> i64 {
+  play_last(bingo).map_or(0, |(_, _, score)| score)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::day4::{first_win_score, first_winner, generator, last_win_score_by_scan,
+                    last_winner, MarkedBoard, parse_bingo, part1, part2, win_order};
+
+  const INPUT: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7
+";
+
+  #[test]
+  fn test_winners() {
+    let bingo = generator(INPUT);
+    assert_eq!((2, 24, 4512), first_winner(&bingo));
+    assert_eq!((1, 13, 1924), last_winner(&bingo));
+    assert_eq!(4512, part1(&bingo));
+    assert_eq!(1924, part2(&bingo));
+  }
+
+  #[test]
+  fn test_win_order() {
+    let bingo = generator(INPUT);
+    assert_eq!(vec![(2, 4512), (0, 2192), (1, 1924)], win_order(&bingo));
+    let bingo = generator(crate::input_for_day(4).expect("Can't find input"));
+    let order = win_order(&bingo);
+    assert_eq!(bingo.boards.len(), order.len());
+    assert_eq!(part1(&bingo), order.first().unwrap().1);
+    assert_eq!(part2(&bingo), order.last().unwrap().1);
+  }
+
+  #[test]
+  fn test_scan() {
+    let bingo = generator(INPUT);
+    assert_eq!(part2(&bingo), last_win_score_by_scan(&bingo));
+    let bingo = generator(crate::input_for_day(4).expect("Can't find input"));
+    assert_eq!(part2(&bingo), last_win_score_by_scan(&bingo));
+  }
+
+  #[test]
+  fn test_small_boards() {
+    let bingo = generator("1,2,3,4,5,6,7,8
+
+1 2
+3 4
+
+5 6
+7 8
+");
+    // the first board wins on the first row
+    assert_eq!((3 + 4) * 2, part1(&bingo));
+    // the second board wins last on its first row
+    assert_eq!((7 + 8) * 6, part2(&bingo));
+  }
+
+  #[test]
+  fn test_diagonals() {
+    let bingo = generator("1,5,9,3,7
+
+1 2 3
+4 5 6
+7 8 9
+");
+    let mut board = MarkedBoard::new(&bingo.boards[0]);
+    for num in [1, 5] {
+      board.mark(num);
+      assert!(!board.won_with_diagonals());
+    }
+    board.mark(9);
+    assert!(!board.won());
+    assert!(board.won_with_diagonals());
+
+    let mut board = MarkedBoard::new(&bingo.boards[0]);
+    for num in [3, 5, 7] {
+      board.mark(num);
+    }
+    assert!(!board.won());
+    assert!(board.won_with_diagonals());
+
+    // the diagonal finishes on 9, which leaves 2+3+4+6+7+8 unmarked
+    assert_eq!(0, first_win_score(&bingo, false));
+    assert_eq!(30 * 9, first_win_score(&bingo, true));
+  }
+
+  #[test]
+  fn test_duplicates() {
+    let input = "2,1,3,4\n\n1 2\n3 4\n\n5 2\n6 2\n";
+    assert_eq!(Some(String::from("Board 2 has 2 more than once")),
+               parse_bingo(input, false).err());
+    // in lenient mode, drawing 2 completes the second board's column
+    let bingo = parse_bingo(input, true).expect("Lenient parse failed");
+    assert_eq!((1, 2, (5 + 6) * 2), first_winner(&bingo));
+  }
+
+  #[test]
+  fn test_line_endings() {
+    let input = INPUT.replace('\n', "\r\n").replace("\r\n\r\n", "\r\n\r\n \r\n\r\n");
+    let bingo = generator(&input);
+    assert_eq!(3, bingo.boards.len());
+    assert_eq!((2, 24, 4512), first_winner(&bingo));
+    assert_eq!((1, 13, 1924), last_winner(&bingo));
+  }
+
+  #[test]
+  fn test_wrapped_moves() {
+    let bingo = generator("1,2,3\n4,5\n\n1 2\n3 4\n");
+    assert_eq!(vec![1, 2, 3, 4, 5], bingo.moves);
+    let bingo = generator("1,2,\n3 ,4\n\n1 2\n3 4\n");
+    assert_eq!(vec![1, 2, 3, 4], bingo.moves);
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(Some(String::from("Board 2 is 2x3 instead of 2x2")),
+               generator_err("1,2\n\n1 2\n3 4\n\n1 2 3\n4 5 6\n"));
+    assert_eq!(Some(String::from("Board 1 row 2 has 3 numbers instead of 2")),
+               generator_err("1,2\n\n1 2\n3 4 5\n"));
+    assert_eq!(Some(String::from("Board 2 has an invalid number \"x\"")),
+               generator_err("1,2\n\n1 2\n3 4\n\n5 x\n7 8\n"));
+    assert_eq!(Some(String::from("Invalid move \"b\"")),
+               generator_err("1,b\n\n1 2\n3 4\n"));
+    assert_eq!(Some(String::from("The game is empty")), generator_err("\n\n"));
+  }
+
+  fn generator_err(input: &str) -> Option<String> {
+    parse_bingo(input, false).err()
+  }
+}
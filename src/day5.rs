@@ -1,15 +1,36 @@
 use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Clone,Debug)]
+/// A point with two coordinates or, for 3D lines, three.
+#[derive(Clone,Debug,Eq,PartialEq)]
 pub struct Point {
-  x: i64,
-  y: i64,
+  pub x: i64,
+  pub y: i64,
+  pub z: Option<i64>,
 }
 
 impl Point {
-  fn parse(s: &str) -> Point {
-    let mut parts = s.split(",").map(|x| x.trim().parse::<i64>().unwrap());
-    Point{x: parts.next().unwrap(), y: parts.next().unwrap()}
+  /// Create a 2D point.
+  pub fn new(x: i64, y: i64) -> Point {
+    Point{x, y, z: None}
+  }
+
+  fn parse(s: &str) -> Result<Point, String> {
+    let coords = s.split(',')
+      .map(|x| x.trim().parse::<i64>())
+      .collect::<Result<Vec<i64>, _>>()
+      .map_err(|_| format!("Invalid point {:?}", s))?;
+    match coords[..] {
+      [x, y] => Ok(Point{x, y, z: None}),
+      [x, y, z] => Ok(Point{x, y, z: Some(z)}),
+      _ => Err(format!("Point {:?} should have two or three coordinates", s)),
+    }
+  }
+
+  /// The z coordinate, where 2D points are at z = 0.
+  fn depth(&self) -> i64 {
+    self.z.unwrap_or(0)
   }
 }
 
@@ -20,27 +41,57 @@ pub struct Line {
 }
 
 impl Line {
-  fn parse(s: &str) -> Line {
-    let mut parts = s.split("->").map(|x| Point::parse(x.trim()));
-    Line{p1: parts.next().unwrap(), p2: parts.next().unwrap()}
+  fn parse(s: &str) -> Result<Line, String> {
+    let (p1, p2) = s.split_once("->")
+      .ok_or_else(|| format!("Missing '->' in {:?}", s))?;
+    let line = Line{p1: Point::parse(p1.trim())?, p2: Point::parse(p2.trim())?};
+    if line.p1.z.is_some() != line.p2.z.is_some() {
+      return Err(format!("The points in {:?} have different dimensions", s));
+    }
+    // each coordinate must change by the same amount or not at all
+    let deltas = line.deltas();
+    let steps = deltas.iter().map(|d| d.abs()).max().unwrap();
+    if deltas.iter().any(|d| *d != 0 && d.abs() != steps) {
+      return Err(format!("{:?} is not axis-aligned or diagonal", s));
+    }
+    Ok(line)
   }
 
-  fn is_vertical(&self) -> bool {
-    self.p1.x == self.p2.x
+  /// The change in each coordinate, which won't overflow even if the
+  /// line spans every i64.
+  fn deltas(&self) -> [i128; 3] {
+    [self.p2.x as i128 - self.p1.x as i128, self.p2.y as i128 - self.p1.y as i128,
+     self.p2.depth() as i128 - self.p1.depth() as i128]
   }
-  
-  fn is_horizontal(&self) -> bool {
-    self.p1.y == self.p2.y
+
+  /// The number of points on the line.
+  fn length(&self) -> u128 {
+    self.deltas().iter().map(|d| d.unsigned_abs()).max().unwrap() + 1
   }
 
-  fn is_upward(&self) -> bool {
-    if self.is_vertical() || self.is_horizontal() {
-      false
-    } else if self.p2.x > self.p1.x {
-      self.p2.y < self.p1.y
-    } else {
-      self.p2.y > self.p1.y
-    }
+  fn is_3d(&self) -> bool {
+    self.p1.z.is_some()
+  }
+
+  /// Does the line run along a single axis?
+  fn is_axis_aligned(&self) -> bool {
+    let changes = [self.p1.x != self.p2.x, self.p1.y != self.p2.y,
+                   self.p1.z != self.p2.z];
+    changes.iter().filter(|&&c| c).count() <= 1
+  }
+
+  /// Iterate through each integer point on the line from p1 to p2.
+  /// The line must be axis-aligned or a 45 degree diagonal.
+  /// A line whose endpoints are equal is a single point, which has
+  /// no direction and zero steps, so it yields just that point.
+  pub fn points(&self) -> impl Iterator<Item = Point> {
+    let [dx, dy, dz] = self.deltas().map(|d| d.signum());
+    let start = self.p1.clone();
+    (0..self.length()).map(move |d| {
+      let d = d as i128;
+      Point{x: (start.x as i128 + d * dx) as i64, y: (start.y as i128 + d * dy) as i64,
+            z: start.z.map(|z| (z as i128 + d * dz) as i64)}
+    })
   }
 
   fn left(&self) -> i64 {
@@ -58,16 +109,21 @@ impl Line {
   fn bottom(&self) -> i64 {
     cmp::max(self.p1.y, self.p2.y)
   }
+
+  fn front(&self) -> i64 {
+    cmp::min(self.p1.depth(), self.p2.depth())
+  }
+
+  fn back(&self) -> i64 {
+    cmp::max(self.p1.depth(), self.p2.depth())
+  }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 enum Bounding {
+  #[default]
   Empty,
-  Box{l:i64, r:i64, t:i64, b:i64},
-}
-
-impl Default for Bounding {
-  fn default() -> Self { Bounding::Empty }
+  Box{l:i64, r:i64, t:i64, b:i64, f:i64, k:i64},
 }
 
 impl Bounding {
@@ -75,104 +131,481 @@ impl Bounding {
     match self {
       Bounding::Empty => Bounding::Box{
         l: line.left(), r: line.right(),
-        t: line.top(), b: line.bottom()},
-      Bounding::Box{l, r, t, b} => Bounding::Box{
+        t: line.top(), b: line.bottom(),
+        f: line.front(), k: line.back()},
+      Bounding::Box{l, r, t, b, f, k} => Bounding::Box{
         l: cmp::min(*l, line.left()),
         r: cmp::max(*r, line.right()),
         t: cmp::min(*t, line.top()),
-        b: cmp::max(*b, line.bottom())}
+        b: cmp::max(*b, line.bottom()),
+        f: cmp::min(*f, line.front()),
+        k: cmp::max(*k, line.back())}
+    }
+  }
+
+  /// The offset of the point in a dense grid, which is ordered by x,
+  /// then y, then z.
+  fn index(&self, p: &Point) -> usize {
+    match self {
+      Bounding::Empty => panic!("Empty bounding box"),
+      Bounding::Box{l, t, b, f, k, ..} =>
+        (((p.x - l) * (b - t + 1) + (p.y - t)) * (k - f + 1) + (p.depth() - f)) as usize,
+    }
+  }
+
+  /// The number of points in the box, if it fits in an i128.
+  fn volume(&self) -> Option<i128> {
+    match self {
+      Bounding::Empty => Some(0),
+      Bounding::Box{l, r, t, b, f, k} =>
+        [(l, r), (t, b), (f, k)].iter()
+          .map(|(&lo, &hi)| hi as i128 - lo as i128 + 1)
+          .try_fold(1i128, |acc, side| acc.checked_mul(side)),
     }
   }
+
+  fn contains(&self, p: &Point) -> bool {
+    match self {
+      Bounding::Empty => false,
+      Bounding::Box{l, r, t, b, f, k} =>
+        (*l..=*r).contains(&p.x) && (*t..=*b).contains(&p.y) &&
+          (*f..=*k).contains(&p.depth()),
+    }
+  }
+}
+
+/// The number of lines covering each point.
+#[derive(Debug)]
+enum Grid {
+  /// a count for every point in the bounding box
+  Dense(Vec<i32>),
+  /// only the points covered by a line
+  Sparse(HashMap<(i64, i64, i64), i32>),
+}
+
+impl Default for Grid {
+  fn default() -> Self { Grid::Dense(Vec::new()) }
 }
 
+/// The number of vent lines that cover each point.
 #[derive(Debug, Default)]
-struct Picture {
+pub struct Picture {
   bounds: Bounding,
-  count: Vec<Vec<i32>>,
+  count: Grid,
+  /// are the lines 3D?
+  is_3d: bool,
 }
 
 impl Picture {
-  fn new(lines: &Vec<Line>) -> Self {
+  /// Bounding boxes with a larger volume than this use a sparse grid.
+  const SPARSE_AREA: i64 = 1 << 24;
+
+  /// The default limit on the number of points that the lines may cover.
+  pub const CELL_BUDGET: u128 = 1 << 28;
+
+  /// Build the picture. Panics if the lines are over the default budget.
+  pub fn new(lines: &[Line]) -> Self {
+    Self::with_budget(lines, Self::CELL_BUDGET).unwrap_or_else(|e| panic!("{}", e))
+  }
+
+  /// Build the picture unless the lines cover more than budget points,
+  /// which would take too much time or memory.
+  pub fn with_budget(lines: &[Line], budget: u128) -> Result<Self, String> {
+    Self::check_budget(lines, budget)?;
+    Ok(Self::with_sparse_area(lines, Self::SPARSE_AREA))
+  }
+
+  fn check_budget(lines: &[Line], budget: u128) -> Result<(), String> {
+    let cells = lines.iter()
+      .try_fold(0u128, |acc, l| acc.checked_add(l.length()))
+      .filter(|&cells| cells <= budget);
+    match cells {
+      Some(_) => Ok(()),
+      None => Err(format!("The lines cover more than the budget of {} points", budget)),
+    }
+  }
+
+  /// Build the picture, using a sparse grid if the bounding box's volume
+  /// is larger than sparse_area.
+  fn with_sparse_area(lines: &[Line], sparse_area: i64) -> Self {
+    let mut result = Self::blank(lines, sparse_area);
+    for l in lines {
+      result.add(l);
+    }
+    result
+  }
+
+  /// Build an empty picture that is big enough to hold the lines.
+  fn blank(lines: &[Line], sparse_area: i64) -> Self {
     let mut result = Picture::default();
     result.bounds = lines.iter().fold(result.bounds, |b, l| b.add(l));
+    result.is_3d = lines.iter().any(|l| l.is_3d());
     match result.bounds {
       Bounding::Empty => {}
-      Bounding::Box{l, r, t, b} =>
-        for _x in l..r+1 {
-          result.count.push(Vec::new());
-          for _y in t..b+1 {
-            result.count.last_mut().unwrap().push(0);
-          }
+      Bounding::Box{l, r, t, b, f, k} =>
+        if result.bounds.volume().is_none_or(|v| v > sparse_area as i128) {
+          result.count = Grid::Sparse(HashMap::new());
+        } else {
+          result.count = Grid::Dense(
+            vec![0; ((r - l + 1) * (b - t + 1) * (k - f + 1)) as usize]);
         }
     }
-    for l in lines {
-      result.add(l);
-    }
     result
   }
 
   fn increment(&mut self, p: &Point) {
-    match self.bounds {
-      Bounding::Empty => {},
-      Bounding::Box{l, r: _, t, b: _} =>
-        self.count[(p.x - l) as usize][(p.y - t) as usize] += 1
+    match (&self.bounds, &mut self.count) {
+      (Bounding::Empty, _) => {},
+      (bounds, Grid::Dense(count)) => count[bounds.index(p)] += 1,
+      (Bounding::Box{..}, Grid::Sparse(count)) =>
+        *count.entry((p.x, p.y, p.depth())).or_insert(0) += 1,
     }
   }
   
   fn add(&mut self, l: &Line) {
-    if l.is_horizontal() {
-      for x in l.left()..l.right()+1 {
-        self.increment(&Point{x: x, y: l.top()})
-      }
-    } else if l.is_vertical() {
-      for y in l.top()..l.bottom()+1 {
-        self.increment(&Point{x: l.left(), y: y})
-      }
-    } else if l.is_upward() {
-      for d in 0..(l.right() - l.left() + 1) {
-        self.increment(&Point{x: l.left() + d,
-                              y: l.bottom() - d});
-      }
-    } else {
-      for d in 0..(l.right() - l.left() + 1) {
-        self.increment(&Point{x: l.left() + d,
-                              y: l.top() + d});
+    for p in l.points() {
+      self.increment(&p);
+    }
+  }
+
+  /// Get the number of lines that cover the point.
+  pub fn get(&self, p: &Point) -> i32 {
+    if !self.bounds.contains(p) {
+      return 0
+    }
+    match (&self.bounds, &self.count) {
+      (Bounding::Empty, _) => 0,
+      (bounds, Grid::Dense(count)) => count[bounds.index(p)],
+      (Bounding::Box{..}, Grid::Sparse(count)) =>
+        *count.get(&(p.x, p.y, p.depth())).unwrap_or(&0),
+    }
+  }
+
+  /// Build a point from the grid's coordinates.
+  fn point(&self, x: i64, y: i64, z: i64) -> Point {
+    Point{x, y, z: if self.is_3d { Some(z) } else { None }}
+  }
+
+  /// Iterate through the points that are covered by at least one line
+  /// along with their counts.
+  pub fn covered(&self) -> Box<dyn Iterator<Item=(Point, i32)> + '_> {
+    match (&self.bounds, &self.count) {
+      (Bounding::Empty, _) => Box::new(std::iter::empty()),
+      (Bounding::Box{l, t, b, f, k, ..}, Grid::Dense(count)) => {
+        let (height, depth) = (b - t + 1, k - f + 1);
+        Box::new(count.iter().enumerate()
+          .filter(|(_, c)| **c > 0)
+          .map(move |(i, &c)| {
+            let i = i as i64;
+            (self.point(l + i / (height * depth), t + i / depth % height, f + i % depth), c)
+          }))
       }
+      (Bounding::Box{..}, Grid::Sparse(count)) =>
+        Box::new(count.iter().map(|(&(x, y, z), &c)| (self.point(x, y, z), c))),
     }
   }
 
+  /// Count the covered points whose count passes f. Uncovered points
+  /// are never counted, so both grids give the same answer.
   fn count<F>(&self, f: F) -> i64
       where F: Fn(i32) -> bool {
-    let mut result: i64 = 0;
-    for col in &self.count {
-      for loc in col {
-        if f(*loc) {
-          result += 1;
+    match &self.count {
+      Grid::Dense(count) =>
+        count.iter().filter(|&&loc| loc > 0 && f(loc)).count() as i64,
+      // the points that aren't in the map have a count of zero
+      Grid::Sparse(count) =>
+        count.values().filter(|&&loc| f(loc)).count() as i64,
+    }
+  }
+}
+
+/// Draw the diagram like the puzzle does, with '.' for uncovered points,
+/// the count for points covered by up to 9 lines, and '#' past that.
+/// 3D pictures are drawn as a series of layers, one for each z.
+impl fmt::Display for Picture {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Bounding::Box{l, r, t, b, f, k} = self.bounds {
+      for z in f..=k {
+        if self.is_3d {
+          if z != f {
+            writeln!(fmt)?;
+          }
+          writeln!(fmt, "z={}", z)?;
+        }
+        for y in t..=b {
+          for x in l..=r {
+            match self.get(&self.point(x, y, z)) {
+              0 => write!(fmt, ".")?,
+              c @ 1..=9 => write!(fmt, "{}", c)?,
+              _ => write!(fmt, "#")?,
+            }
+          }
+          writeln!(fmt)?;
         }
       }
     }
-    result
+    Ok(())
   }
 }
 
+/// Parse the lines of vents, reporting the first bad line.
+/// The lines may be 2D or 3D, but they must all be the same.
+pub fn parse_lines(data: &str) -> Result<Vec<Line>, String> {
+  let lines: Vec<Line> = data.lines()
+    .enumerate()
+    .map(|(i, x)| (i, x.trim()))
+    .filter(|(_, x)| !x.is_empty())
+    .map(|(i, x)| Line::parse(x).map_err(|e| format!("Line {}: {}", i + 1, e)))
+    .collect::<Result<_, _>>()?;
+  if lines.iter().any(|l| l.is_3d() != lines[0].is_3d()) {
+    return Err(String::from("The lines mix 2D and 3D points"));
+  }
+  Ok(lines)
+}
+
 pub fn generator(data: &str) -> Vec<Line> {
-  data.lines()
-    .map(|x| x.trim())
-    .filter(|x| x.len() > 0)
-    .map(|x| Line::parse(x))
-    .collect()
+  parse_lines(data).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Count the points that are covered by at least min lines. Diagonal
+/// lines are ignored unless include_diagonals is set. A min below 1
+/// counts every covered point.
+pub fn count_overlaps(lines: &[Line], include_diagonals: bool, min: i32) -> i64 {
+  let pic = if include_diagonals {
+    Picture::new(lines)
+  } else {
+    let horiz_or_vert: Vec<Line> = lines.iter()
+      .filter(|x| x.is_axis_aligned())
+      .cloned()
+      .collect();
+    Picture::new(&horiz_or_vert)
+  };
+  pic.count(|x| x >= min)
 }
 
-pub fn part1(lines: &Vec<Line>) -> i64 {
-  let horiz_or_vert: Vec<Line> = lines.iter()
-    .filter(|x| x.is_horizontal() || x.is_vertical())
-    .cloned()
-    .collect();
-  let pic = Picture::new(&horiz_or_vert);
-  pic.count(|x| x > 1)
+/// Compute both parts with a single grid by drawing the axis-aligned
+/// lines first and then adding the diagonals.
+pub fn counts(lines: &[Line]) -> (i64, i64) {
+  Picture::check_budget(lines, Picture::CELL_BUDGET).unwrap_or_else(|e| panic!("{}", e));
+  let mut pic = Picture::blank(lines, Picture::SPARSE_AREA);
+  let (aligned, diagonal): (Vec<&Line>, Vec<&Line>) =
+    lines.iter().partition(|x| x.is_axis_aligned());
+  for l in aligned {
+    pic.add(l);
+  }
+  let without_diagonals = pic.count(|x| x > 1);
+  for l in diagonal {
+    pic.add(l);
+  }
+  (without_diagonals, pic.count(|x| x > 1))
 }
 
-pub fn part2(lines: &Vec<Line>) -> i64 {
-  let pic = Picture::new(lines);
-  pic.count(|x| x > 1)
+pub fn part1(lines: &[Line]) -> i64 {
+  count_overlaps(lines, false, 2)
+}
+
+pub fn part2(lines: &[Line]) -> i64 {
+  count_overlaps(lines, true, 2)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::day5::{count_overlaps, counts, generator, Grid, Line, parse_lines, part1, part2, Picture,
+                    Point};
+
+  const INPUT: &str = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2
+";
+
+  #[test]
+  fn test_parts() {
+    let lines = generator(INPUT);
+    assert_eq!(5, part1(&lines));
+    assert_eq!(12, part2(&lines));
+    assert_eq!((5, 12), counts(&lines));
+  }
+
+  #[test]
+  fn test_counts() {
+    let lines = generator(crate::input_for_day(5).expect("Can't find input"));
+    assert_eq!((part1(&lines), part2(&lines)), counts(&lines));
+  }
+
+  fn points(line: &str) -> Vec<(i64, i64)> {
+    Line::parse(line).unwrap().points().map(|p| (p.x, p.y)).collect()
+  }
+
+  #[test]
+  fn test_points() {
+    assert_eq!(vec![(1, 1), (1, 2), (1, 3)], points("1,1 -> 1,3"));
+    assert_eq!(vec![(3, 4), (2, 4), (1, 4)], points("3,4 -> 1,4"));
+    assert_eq!(vec![(1, 1), (2, 2), (3, 3)], points("1,1 -> 3,3"));
+    assert_eq!(vec![(9, 7), (8, 8), (7, 9)], points("9,7 -> 7,9"));
+    assert_eq!(vec![(5, 5)], points("5,5 -> 5,5"));
+  }
+
+  #[test]
+  fn test_single_point() {
+    let lines = generator("5,5 -> 5,5\n");
+    // a point doesn't change along any axis
+    assert!(lines[0].is_axis_aligned());
+    let pic = Picture::new(&lines);
+    assert_eq!(vec![(Point::new(5, 5), 1)], pic.covered().collect::<Vec<_>>());
+    assert_eq!("1\n", pic.to_string());
+    assert_eq!(1, count_overlaps(&lines, false, 1));
+    assert_eq!(1, count_overlaps(&lines, true, 1));
+  }
+
+  #[test]
+  fn test_negative() {
+    let lines = generator("-2,-2 -> 2,2\n-2,2 -> 2,-2\n-3,0 -> 3,0\n");
+    assert_eq!(vec![(-2, 2), (-1, 1), (0, 0), (1, -1), (2, -2)], points("-2,2 -> 2,-2"));
+    for pic in [Picture::new(&lines), Picture::with_sparse_area(&lines, 0)] {
+      assert_eq!(3, pic.get(&Point::new(0, 0)));
+      assert_eq!(1, pic.get(&Point::new(-3, 0)));
+      assert_eq!(1, pic.get(&Point::new(-2, -2)));
+      assert_eq!(0, pic.get(&Point::new(-4, 0)));
+      assert_eq!(".1...1.
+..1.1..
+1113111
+..1.1..
+.1...1.
+", pic.to_string());
+    }
+    assert_eq!(15, count_overlaps(&lines, true, 1));
+    assert_eq!(1, part2(&lines));
+    assert_eq!(0, part1(&lines));
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_eq!(Some(String::from("Line 2: Missing '->' in \"1,2 3,4\"")),
+               parse_lines("0,0 -> 1,1\n1,2 3,4\n").err());
+    assert_eq!(Some(String::from("Line 1: Invalid point \"1,x\"")),
+               parse_lines("1,x -> 3,4\n").err());
+    assert_eq!(Some(String::from("Line 1: Point \"3\" should have two or three coordinates")),
+               parse_lines("1,2 -> 3\n").err());
+  }
+
+  #[test]
+  fn test_3d() {
+    let lines = generator("0,0,0 -> 2,0,0\n1,0,0 -> 1,0,2\n0,0,2 -> 2,2,0\n2,0,2 -> 0,2,0\n");
+    assert_eq!(vec![Point{x: 1, y: 0, z: Some(0)}, Point{x: 1, y: 0, z: Some(1)},
+                    Point{x: 1, y: 0, z: Some(2)}],
+               lines[1].points().collect::<Vec<_>>());
+    for pic in [Picture::new(&lines), Picture::with_sparse_area(&lines, 0)] {
+      assert_eq!(2, pic.get(&Point{x: 1, y: 0, z: Some(0)}));
+      assert_eq!(2, pic.get(&Point{x: 1, y: 1, z: Some(1)}));
+      assert_eq!("z=0\n121\n...\n1.1\n\nz=1\n.1.\n.2.\n...\n\nz=2\n111\n...\n...\n",
+                 pic.to_string());
+    }
+    assert_eq!(1, part1(&lines));
+    assert_eq!(2, part2(&lines));
+  }
+
+  #[test]
+  fn test_3d_errors() {
+    assert_eq!(Some(String::from("Line 1: The points in \"0,0,0 -> 1,1\" have different dimensions")),
+               parse_lines("0,0,0 -> 1,1\n").err());
+    assert_eq!(Some(String::from("Line 1: \"0,0,0 -> 1,2,2\" is not axis-aligned or diagonal")),
+               parse_lines("0,0,0 -> 1,2,2\n").err());
+    assert_eq!(Some(String::from("The lines mix 2D and 3D points")),
+               parse_lines("0,0 -> 1,1\n0,0,0 -> 1,1,1\n").err());
+  }
+
+  #[test]
+  fn test_budget() {
+    let lines = generator("-9223372036854775808,0 -> 9223372036854775807,0\n0,0 -> 0,1\n");
+    assert_eq!(1 << 64, lines[0].length());
+    // the points of a line spanning every i64 don't overflow
+    assert_eq!(vec![Point::new(i64::MIN, 0), Point::new(i64::MIN + 1, 0)],
+               lines[0].points().take(2).collect::<Vec<Point>>());
+    let reversed = generator("9223372036854775807,9223372036854775807 -> \
+                              -9223372036854775808,-9223372036854775808\n");
+    assert_eq!(vec![Point::new(i64::MAX, i64::MAX), Point::new(i64::MAX - 1, i64::MAX - 1)],
+               reversed[0].points().take(2).collect::<Vec<Point>>());
+    assert_eq!(Some(String::from("The lines cover more than the budget of 268435456 points")),
+               Picture::with_budget(&lines, Picture::CELL_BUDGET).err());
+    let lines = generator("0,0 -> 0,9\n0,0 -> 9,0\n");
+    assert!(Picture::with_budget(&lines, 19).is_err());
+    assert_eq!(1, Picture::with_budget(&lines, 20).unwrap().count(|x| x > 1));
+  }
+
+  #[test]
+  fn test_count_overlaps() {
+    let lines = generator(INPUT);
+    assert_eq!(21, count_overlaps(&lines, false, 1));
+    assert_eq!(39, count_overlaps(&lines, true, 1));
+    assert_eq!(2, count_overlaps(&lines, true, 3));
+    assert_eq!(0, count_overlaps(&lines, true, 4));
+  }
+
+  #[test]
+  fn test_display() {
+    let lines = generator(INPUT);
+    let expected = "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111....
+";
+    assert_eq!(expected, Picture::new(&lines).to_string());
+    assert_eq!(expected, Picture::with_sparse_area(&lines, 0).to_string());
+  }
+
+  #[test]
+  fn test_covered() {
+    let lines = generator(INPUT);
+    for pic in [Picture::new(&lines), Picture::with_sparse_area(&lines, 0)] {
+      let mut covered: Vec<(Point, i32)> = pic.covered().collect();
+      covered.sort_by_key(|(p, _)| (p.y, p.x));
+      assert_eq!(39, covered.len());
+      assert_eq!((Point::new(0, 0), 1), covered[0]);
+      assert_eq!(3, pic.get(&Point::new(4, 4)));
+      assert_eq!(0, pic.get(&Point::new(100, 4)));
+    }
+  }
+
+  #[test]
+  fn test_sparse() {
+    let lines = generator(INPUT);
+    let sparse = Picture::with_sparse_area(&lines, 0);
+    assert!(matches!(sparse.count, Grid::Sparse(_)));
+    let dense = Picture::new(&lines);
+    assert!(matches!(dense.count, Grid::Dense(_)));
+    assert_eq!(dense.count(|x| x > 1), sparse.count(|x| x > 1));
+    assert_eq!(dense.count(|x| x > 0), sparse.count(|x| x > 0));
+    // uncovered points in the bounding box aren't counted by either grid
+    let lines = generator("0,0 -> 0,2\n5,5 -> 5,5\n");
+    let sparse = Picture::with_sparse_area(&lines, 0);
+    let dense = Picture::new(&lines);
+    assert!(matches!(dense.count, Grid::Dense(_)));
+    for min in [-1, 0, 1] {
+      assert_eq!(4, dense.count(|x| x >= min));
+      assert_eq!(4, sparse.count(|x| x >= min));
+    }
+    assert_eq!(4, count_overlaps(&lines, true, 0));
+  }
+
+  #[test]
+  fn test_far_apart() {
+    let lines = generator("0,0 -> 0,10\n0,5 -> 0,20\n\
+                           100000,100000 -> 100010,100000\n100005,100000 -> 100020,100000\n");
+    let pic = Picture::new(&lines);
+    assert!(matches!(pic.count, Grid::Sparse(_)));
+    assert_eq!(6 + 6, part1(&lines));
+  }
 }
@@ -1,57 +1,355 @@
+use num_bigint::BigUint;
+
 const BIRTH_TO_BIRTH: i32 = 9;
 const GENERATION: i32 = 7;
-  
+
+/// The integer type used to count the fish. The population grows
+/// exponentially, which overflows an i64 after roughly 450 days and a
+/// u128 after roughly 1000, so longer horizons need a BigUint.
+pub trait Count: Clone + Default {
+  fn one() -> Self;
+  fn checked_add(&self, other: &Self) -> Option<Self>;
+  fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
+
+impl Count for u128 {
+  fn one() -> Self {
+    1
+  }
+
+  fn checked_add(&self, other: &Self) -> Option<Self> {
+    u128::checked_add(*self, *other)
+  }
+
+  fn checked_mul(&self, other: &Self) -> Option<Self> {
+    u128::checked_mul(*self, *other)
+  }
+}
+
+impl Count for BigUint {
+  fn one() -> Self {
+    BigUint::from(1u32)
+  }
+
+  fn checked_add(&self, other: &Self) -> Option<Self> {
+    Some(self + other)
+  }
+
+  fn checked_mul(&self, other: &Self) -> Option<Self> {
+    Some(self * other)
+  }
+}
+
+/// The number of fish with each timer value. The counts default to u128,
+/// which panics if the population overflows.
 #[derive(Clone,Debug,Default)]
-pub struct Ocean {
-  count: Vec<i64>,
+pub struct Ocean<T: Count = u128> {
+  count: Vec<T>,
   age: i32,
 }
 
-impl Ocean {
-  fn add(&mut self, age: i32, cnt: i64) {
+impl<T: Count> Ocean<T> {
+  fn add(&mut self, age: i32, cnt: &T) {
     while self.count.len() <= age as usize {
-      self.count.push(0);
+      self.count.push(T::default());
     }
-    self.count[age as usize] += cnt;
+    self.count[age as usize] = self.count[age as usize].checked_add(cnt)
+      .unwrap_or_else(|| panic!("The population overflowed on day {}", self.age));
   }
 
   fn age(&mut self) {
-    let children = self.count.remove(0);
-    self.add(BIRTH_TO_BIRTH - 1, children);
-    self.add(GENERATION - 1, children);
+    // an empty ocean has no fish at timer zero
+    let children = if self.count.is_empty() { T::default() } else { self.count.remove(0) };
+    self.add(BIRTH_TO_BIRTH - 1, &children);
+    self.add(GENERATION - 1, &children);
     self.age += 1;
   }
 
-  fn total(&self) -> i64 {
-    self.count.iter().fold(0, |acc, c| acc + c)
+  /// Add count new fish with the given timer. Panics if the timer
+  /// is negative.
+  pub fn spawn(&mut self, age: i32, count: T) {
+    assert!(age >= 0, "Timer {} is negative", age);
+    self.add(age, &count);
+  }
+
+  fn total(&self) -> T {
+    self.count.iter()
+      .try_fold(T::default(), |acc, c| acc.checked_add(c))
+      .unwrap_or_else(|| panic!("The population overflowed on day {}", self.age))
+  }
+
+  /// Convert the counts to a different integer type, such as BigUint.
+  pub fn convert<U: Count + From<T>>(&self) -> Ocean<U> {
+    Ocean{count: self.count.iter().cloned().map(U::from).collect(), age: self.age}
   }
 }
 
-pub fn generator(data: &str) -> Ocean {
+/// Parse the initial timers, which must be from 0 to 8. The lenient mode
+/// allows longer timers for experiments, but never negative ones.
+pub fn parse_ocean(data: &str, lenient: bool) -> Result<Ocean, String> {
   let first = data.lines()
     .map(|x| x.trim())
-    .filter(|x| x.len() > 0).next().unwrap();
+    .find(|x| !x.is_empty())
+    .ok_or("The input is empty")?;
+  let max_timer = if lenient { i32::MAX } else { BIRTH_TO_BIRTH - 1 };
   let mut ocean = Ocean::default();
-  for fish in first.split(",") {
-    let age = fish.trim().parse::<i32>().unwrap();
-    ocean.add(age, 1);
+  for fish in first.split(',') {
+    let age = fish.trim().parse::<i32>()
+      .map_err(|_| format!("Invalid timer {:?}", fish.trim()))?;
+    if !(0..=max_timer).contains(&age) {
+      return Err(format!("Timer {} is outside of 0..={}", age, max_timer));
+    }
+    ocean.add(age, &1);
   }
-  ocean
+  Ok(ocean)
+}
+
+pub fn generator(data: &str) -> Ocean {
+  parse_ocean(data, false).unwrap_or_else(|e| panic!("{}", e))
 }
 
-pub fn part1(fishes: &Ocean) -> i64 {
-  let mut ocean = (*fishes).clone();
-  for _ in 0..80 {
+// Age a copy of the ocean by the given number of days.
+fn aged<T: Count>(ocean: &Ocean<T>, days: u32) -> Ocean<T> {
+  let mut ocean = ocean.clone();
+  for _ in 0..days {
     ocean.age();
   }
+  ocean
+}
+
+/// Past this many days, population_after uses matrix exponentiation.
+const MATRIX_DAYS: u32 = 512;
+
+/// Count the fish after the given number of days. Panics if the
+/// count doesn't fit in T, so use a BigUint ocean for long horizons.
+pub fn population_after<T: Count>(ocean: &Ocean<T>, days: u32) -> T {
+  if days > MATRIX_DAYS {
+    population_after_matrix(ocean, days)
+  } else {
+    aged(ocean, days).total()
+  }
+}
+
+/// Count the fish after the given number of days when extra fish are
+/// added along the way. Each event is (day, timer, count), and the fish
+/// are added after that many days have passed.
+pub fn simulate_with_events<T: Count>(ocean: &Ocean<T>, days: u32,
+                                      events: &[(u32, i32, T)]) -> T {
+  let mut ocean = ocean.clone();
+  for day in 0..=days {
+    for (_, age, count) in events.iter().filter(|(when, _, _)| *when == day) {
+      ocean.spawn(*age, count.clone());
+    }
+    if day < days {
+      ocean.age();
+    }
+  }
   ocean.total()
 }
 
-pub fn part2(fishes: &Ocean) -> i64 {
-  let mut ocean = (*fishes).clone();
-  for _ in 0..256 {
+/// Record the total number of fish after each day from 1 to days.
+pub fn history<T: Count>(ocean: &Ocean<T>, days: u32) -> Vec<T> {
+  let mut ocean = ocean.clone();
+  (0..days).map(|_| {
+    ocean.age();
+    ocean.total()
+  }).collect()
+}
+
+const BUCKETS: usize = BIRTH_TO_BIRTH as usize;
+
+/// A transition between the counts for each timer value.
+type Matrix<T> = [[T; BUCKETS]; BUCKETS];
+
+fn overflow<T>() -> T {
+  panic!("The population overflowed")
+}
+
+/// Compute the sum of the products of the pairs, panicking on overflow.
+fn dot<'a, T: Count + 'a>(mut pairs: impl Iterator<Item = (&'a T, &'a T)>) -> T {
+  pairs
+    .try_fold(T::default(), |acc, (l, r)| l.checked_mul(r).and_then(|x| acc.checked_add(&x)))
+    .unwrap_or_else(overflow)
+}
+
+fn multiply<T: Count>(left: &Matrix<T>, right: &Matrix<T>) -> Matrix<T> {
+  std::array::from_fn(|i| std::array::from_fn(|j|
+    dot((0..BUCKETS).map(|k| (&left[i][k], &right[k][j])))))
+}
+
+/// Build the matrix that ages the ocean by one day. Row i says which
+/// of yesterday's timers become timer i.
+fn one_day<T: Count>() -> Matrix<T> {
+  let mut result: Matrix<T> = Default::default();
+  for (i, row) in result.iter_mut().enumerate().take(BUCKETS - 1) {
+    row[i + 1] = T::one();
+  }
+  result[(GENERATION - 1) as usize][0] = T::one();
+  result[BUCKETS - 1][0] = T::one();
+  result
+}
+
+/// Compute the transition for the given number of days by squaring.
+fn matrix_power<T: Count>(days: u32) -> Matrix<T> {
+  let mut result: Matrix<T> = Default::default();
+  for (i, row) in result.iter_mut().enumerate() {
+    row[i] = T::one();
+  }
+  let mut square = one_day();
+  let mut remaining = days;
+  while remaining > 0 {
+    if remaining & 1 == 1 {
+      result = multiply(&result, &square);
+    }
+    remaining >>= 1;
+    if remaining > 0 {
+      square = multiply(&square, &square);
+    }
+  }
+  result
+}
+
+/// Count the fish after the given number of days in O(log days) time
+/// by raising the one day transition matrix to a power.
+pub fn population_after_matrix<T: Count>(ocean: &Ocean<T>, days: u32) -> T {
+  // age any fish with unusually long timers until they fit the matrix
+  let mut ocean = ocean.clone();
+  let mut days = days;
+  while ocean.count.len() > BUCKETS && days > 0 {
     ocean.age();
+    days -= 1;
   }
-  ocean.total()
+  if ocean.count.len() > BUCKETS {
+    return ocean.total()
+  }
+  let transition: Matrix<T> = matrix_power(days);
+  let rows: Vec<T> = transition.iter()
+    .map(|row| dot(row.iter().zip(&ocean.count)))
+    .collect();
+  rows.iter()
+    .try_fold(T::default(), |acc, x| acc.checked_add(x))
+    .unwrap_or_else(overflow)
+}
+
+/// Count the fish with each timer value after the given number of days.
+/// The timers go from 0 to 8, or longer if a lenient parse allowed
+/// longer timers and those fish haven't had children yet.
+pub fn distribution_after<T: Count>(ocean: &Ocean<T>, days: u32) -> Vec<T> {
+  let mut result = aged(ocean, days).count;
+  if result.len() < BUCKETS {
+    result.resize(BUCKETS, T::default());
+  }
+  result
+}
+
+pub fn part1(fishes: &Ocean) -> u64 {
+  u64::try_from(population_after(fishes, 80)).expect("Population doesn't fit in a u64")
 }
 
+pub fn part2(fishes: &Ocean) -> u64 {
+  u64::try_from(population_after(fishes, 256)).expect("Population doesn't fit in a u64")
+}
+
+#[cfg(test)]
+mod tests {
+  use num_bigint::BigUint;
+  use crate::day6::{aged, distribution_after, generator, history, Ocean, parse_ocean, part1,
+                    part2, population_after, population_after_matrix, simulate_with_events};
+
+  const INPUT: &str = "3,4,3,1,2\n";
+
+  #[test]
+  fn test_population() {
+    let ocean = generator(INPUT);
+    assert_eq!(5, population_after(&ocean, 0));
+    assert_eq!(26, population_after(&ocean, 18));
+    assert_eq!(5934, part1(&ocean));
+    assert_eq!(26984457539, part2(&ocean));
+  }
+
+  #[test]
+  fn test_distribution() {
+    let ocean = generator(INPUT);
+    assert_eq!(vec![0, 1, 1, 2, 1, 0, 0, 0, 0], distribution_after(&ocean, 0));
+    let distribution = distribution_after(&ocean, 18);
+    assert_eq!(vec![3, 5, 3, 2, 2, 1, 5, 1, 4], distribution);
+    assert_eq!(population_after(&ocean, 18), distribution.iter().sum());
+    // long timers from a lenient parse are kept
+    let ocean = parse_ocean("12,1\n", true).expect("Lenient parse failed");
+    let distribution = distribution_after(&ocean, 2);
+    assert_eq!(vec![0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 1], distribution);
+    assert_eq!(population_after(&ocean, 2), distribution.iter().sum());
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(Some(String::from("Timer 10 is outside of 0..=8")),
+               parse_ocean("3,10,1\n", false).err());
+    assert_eq!(Some(String::from("Timer -1 is outside of 0..=2147483647")),
+               parse_ocean("3,-1\n", true).err());
+    assert_eq!(Some(String::from("Invalid timer \"x\"")), parse_ocean("3,x\n", false).err());
+    assert_eq!(Some(String::from("The input is empty")), parse_ocean("\n", false).err());
+    // a fish with a timer of 10 has its first child on day 11
+    let ocean = parse_ocean("10\n", true).expect("Lenient parse failed");
+    assert_eq!(vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2], history(&ocean, 12));
+    assert_eq!(population_after(&ocean, 100), population_after_matrix(&ocean, 100));
+  }
+
+  #[test]
+  fn test_events() {
+    let ocean = generator(INPUT);
+    assert_eq!(26 + 10, simulate_with_events(&ocean, 18, &[(18, 8, 10)]));
+    let mut extra = Ocean::default();
+    extra.spawn(8, 10);
+    assert_eq!(population_after(&ocean, 80) + population_after(&extra, 62),
+               simulate_with_events(&ocean, 80, &[(18, 8, 10)]));
+    // events after the end are ignored
+    assert_eq!(5934, simulate_with_events(&ocean, 80, &[(81, 8, 10)]));
+    // an empty ocean can be aged before the first fish arrive
+    assert_eq!(5, simulate_with_events(&Ocean::default(), 3, &[(1, 8, 5)]));
+    assert_eq!(0, population_after(&Ocean::<u128>::default(), 10));
+  }
+
+  #[test]
+  fn test_history() {
+    let ocean = generator(INPUT);
+    let totals = history(&ocean, 80);
+    assert_eq!(80, totals.len());
+    assert_eq!(&[5, 6, 7, 9, 10], &totals[..5]);
+    assert!(totals.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(Some(&population_after(&ocean, 80)), totals.last());
+    assert!(history(&ocean, 0).is_empty());
+  }
+
+  #[test]
+  fn test_matrix() {
+    for input in [INPUT, crate::input_for_day(6).expect("Can't find input")] {
+      let ocean = generator(input);
+      for days in [0, 1, 18, 80, 256] {
+        assert_eq!(aged(&ocean, days).total(), population_after_matrix(&ocean, days));
+      }
+    }
+    let ocean = generator(INPUT);
+    assert_eq!(aged(&ocean, 900).total(), population_after(&ocean, 900));
+  }
+
+  #[test]
+  fn test_long_horizon() {
+    let ocean = generator(crate::input_for_day(6).expect("Can't find input"));
+    assert_eq!(1629570219571, part2(&ocean));
+    assert!(population_after(&ocean, 600) > i64::MAX as u128);
+  }
+
+  #[test]
+  fn test_big_counts() {
+    let ocean = generator(INPUT);
+    let big: Ocean<BigUint> = ocean.convert();
+    assert_eq!(BigUint::from(26984457539u64), population_after(&big, 256));
+    assert_eq!(BigUint::from(population_after(&ocean, 900)), population_after(&big, 900));
+    // u128 overflows long before 2000 days, but a BigUint doesn't
+    let total = population_after(&big, 2000);
+    assert!(total > BigUint::from(u128::MAX));
+    assert_eq!(aged(&big, 2000).total(), total);
+    assert_eq!(total, distribution_after(&big, 2000).iter().sum());
+  }
+}
@@ -1,36 +1,228 @@
+/// A crab's position and how much its fuel costs are multiplied by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Crab {
+  pub position: i32,
+  pub weight: i64,
+}
+
+impl Crab {
+  /// Parse either "position" or "position:weight".
+  fn parse(s: &str) -> Result<Crab, String> {
+    let (position, weight) = match s.split_once(':') {
+      Some((position, weight)) => (position.trim(), Some(weight.trim())),
+      None => (s, None),
+    };
+    let position = position.parse::<i32>()
+      .map_err(|_| format!("Invalid position {:?}", position))?;
+    let weight = match weight {
+      Some(weight) => weight.parse::<i64>().ok().filter(|w| *w >= 0)
+        .ok_or_else(|| format!("Invalid weight {:?}", weight))?,
+      None => 1,
+    };
+    Ok(Crab{position, weight})
+  }
+}
 
-fn cost(n: i32) -> i32 {
+fn cost(n: i32) -> i64 {
+  let n = n as i64;
   (n + 1) * n / 2
 }
 
-fn total_cost(posns: &Vec<i32>, goal: i32) -> i32 {
-  posns.iter().fold(0, |total, x| total + cost((x - goal).abs()))
+/// The total cost for every crab to move to goal.
+fn total_cost<F: Fn(i32) -> i64>(crabs: &[Crab], goal: i32, cost: &F) -> i64 {
+  crabs.iter().map(|x| x.weight * cost((x.position - goal).abs())).sum()
 }
 
-pub fn generator(data: &str) -> Vec<i32> {
+/// Parse the crabs and sort them by position. Each crab is either a
+/// position or a position and weight separated by a colon.
+pub fn parse_crabs(data: &str) -> Result<Vec<Crab>, String> {
   let first = data.lines()
     .map(|x| x.trim())
-    .filter(|x| x.len() > 0).next().unwrap();
-  let mut result: Vec<i32> = first.split(",")
-    .map(|x| x.trim().parse::<i32>().unwrap())
-    .collect();
-  result.sort();
-  result
+    .find(|x| !x.is_empty())
+    .ok_or("There are no crabs")?;
+  let mut result: Vec<Crab> = first.split(',')
+    .map(|x| Crab::parse(x.trim()))
+    .collect::<Result<_, _>>()?;
+  result.sort_by_key(|x| x.position);
+  Ok(result)
 }
 
-pub fn part1(crabs: &Vec<i32>) -> i32 {
-  let median = crabs[crabs.len() / 2];
-  crabs.iter().fold(0, |cost, x| cost + (x - median).abs())
+pub fn generator(data: &str) -> Vec<Crab> {
+  parse_crabs(data).unwrap_or_else(|e| panic!("{}", e))
 }
 
-pub fn part2(crabs: &Vec<i32>) -> i32 {
-  let mut best_cost = i32::MAX;
-  for g in crabs[0]..crabs[crabs.len() - 1] {
-    let new_cost = total_cost(&crabs, g);
-    if new_cost < best_cost {
-      best_cost = new_cost;
-    }
+/// Find the cheapest position for the sorted crabs to line up and its
+/// total cost, where cost gives the fuel to move a distance. The cost
+/// must not decrease with distance, so that the best position is between
+/// the first and last crab, and each of those positions is tried. It
+/// returns the first of the cheapest positions. With no crabs, there is
+/// nothing to move, so the result is (0, 0). Each crab's cost is
+/// multiplied by its weight.
+pub fn best_position(crabs: &[Crab], cost: impl Fn(i32) -> i64) -> (i32, i64) {
+  match (crabs.first(), crabs.last()) {
+    (Some(first), Some(last)) => (first.position..=last.position)
+      .map(|g| (g, total_cost(crabs, g, &cost)))
+      .min_by_key(|&(_, total)| total)
+      .unwrap(),
+    _ => (0, 0),
   }
-  best_cost
 }
 
+/// Find the cheapest total cost for the sorted crabs to line up.
+pub fn min_total_cost<F: Fn(i32) -> i64>(crabs: &[Crab], cost: F) -> i64 {
+  best_position(crabs, cost).1
+}
+
+/// The total weight of the crabs.
+fn total_weight(crabs: &[Crab]) -> i64 {
+  crabs.iter().map(|x| x.weight).sum()
+}
+
+pub fn part1(crabs: &[Crab]) -> i64 {
+  // The best position for linear costs is the weighted median. With an
+  // even number of crabs, every position between the two middle crabs
+  // has the same cost, and this picks the lower one.
+  let half = total_weight(crabs);
+  let mut seen = 0;
+  match crabs.iter().find(|x| { seen += x.weight; 2 * seen >= half }) {
+    Some(median) => total_cost(crabs, median.position, &|distance| distance as i64),
+    None => 0,
+  }
+}
+
+/// Find the cheapest triangular cost by trying every position, which is
+/// slow, but useful for checking part2.
+pub fn part2_brute_force(crabs: &[Crab]) -> i64 {
+  min_total_cost(crabs, cost)
+}
+
+pub fn part2(crabs: &[Crab]) -> i64 {
+  // The real-valued minimum is within 0.5 of the weighted mean, so the
+  // best integer position is within one of floor(mean) or ceil(mean).
+  let weight = total_weight(crabs);
+  if weight == 0 {
+    return 0
+  }
+  let sum: i64 = crabs.iter().map(|x| x.weight * x.position as i64).sum();
+  let floor = sum.div_euclid(weight) as i32;
+  let ceil = floor + if sum.rem_euclid(weight) == 0 { 0 } else { 1 };
+  (floor - 1..=ceil + 1)
+    .map(|g| total_cost(crabs, g, &cost))
+    .min()
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::day7::{best_position, cost, Crab, generator, min_total_cost, parse_crabs, part1,
+                    part2, part2_brute_force, total_cost};
+
+  const INPUT: &str = "16,1,2,0,4,2,7,1,2,14\n";
+
+  #[test]
+  fn test_parts() {
+    let crabs = generator(INPUT);
+    assert_eq!(37, part1(&crabs));
+    assert_eq!(168, part2(&crabs));
+  }
+
+  #[test]
+  fn test_empty() {
+    assert_eq!(Some(String::from("There are no crabs")), parse_crabs("").err());
+    assert_eq!(Some(String::from("There are no crabs")), parse_crabs("\n \n").err());
+    assert_eq!(Some(String::from("Invalid position \"\"")), parse_crabs("1,,2").err());
+    assert_eq!((0, 0), best_position(&[], cost));
+    assert_eq!(0, part1(&[]));
+    assert_eq!(0, part2(&[]));
+    assert_eq!(0, part2_brute_force(&[]));
+  }
+
+  #[test]
+  fn test_large_costs() {
+    let crabs = generator("0,100000\n");
+    assert_eq!(100000, part1(&crabs));
+    // each crab moves 50000 for 1250025000 fuel
+    assert_eq!(2500050000, part2(&crabs));
+    assert_eq!(part2_brute_force(&crabs), part2(&crabs));
+  }
+
+  #[test]
+  fn test_weights() {
+    assert_eq!(generator(INPUT), generator("16:1,1,2,0:1,4,2,7,1,2,14\n"));
+    assert_eq!(vec![Crab{position: 1, weight: 3}, Crab{position: 5, weight: 0}],
+               generator("5:0, 1 : 3\n"));
+    // a weight is the same as that many crabs at the position
+    let weighted = generator("0:3,10\n");
+    let repeated = generator("0,0,0,10\n");
+    assert_eq!(best_position(&repeated, |d| d as i64), best_position(&weighted, |d| d as i64));
+    assert_eq!(best_position(&repeated, cost), best_position(&weighted, cost));
+    assert_eq!((0, 10), best_position(&weighted, |d| d as i64));
+    assert_eq!(Some(String::from("Invalid weight \"-1\"")), parse_crabs("1:-1").err());
+    assert_eq!(Some(String::from("Invalid position \"x\"")), parse_crabs("x:2").err());
+  }
+
+  #[test]
+  fn test_best_position() {
+    let crabs = generator(INPUT);
+    assert_eq!((2, 37), best_position(&crabs, |d| d as i64));
+    assert_eq!((5, 168), best_position(&crabs, cost));
+    let crabs = generator(crate::input_for_day(7).expect("Can't find input"));
+    assert_eq!(part1(&crabs), best_position(&crabs, |d| d as i64).1);
+    assert_eq!(part2(&crabs), best_position(&crabs, cost).1);
+  }
+
+  #[test]
+  fn test_even_median() {
+    let crabs = generator("10,1,3,2\n");
+    // both middle crabs (2 and 3) and every point between them are optimal
+    assert_eq!(10, total_cost(&crabs, 2, &|d| d as i64));
+    assert_eq!(10, total_cost(&crabs, 3, &|d| d as i64));
+    assert_eq!(10, part1(&crabs));
+    let crabs = generator("1,1,5,5\n");
+    for g in 1..=5 {
+      assert_eq!(8, total_cost(&crabs, g, &|d| d as i64));
+    }
+    assert_eq!(8, part1(&crabs));
+  }
+
+  #[test]
+  fn test_best_at_end() {
+    // the cheapest position is the last crab's
+    assert_eq!(1, part2(&generator("9,10,10,10,10,10,10,10,10,10\n")));
+    assert_eq!(0, part2(&generator("5\n")));
+    assert_eq!(1, part2_brute_force(&generator("9,10,10,10,10,10,10,10,10,10\n")));
+  }
+
+  #[test]
+  fn test_brute_force() {
+    for input in [INPUT, "0,0,0,0,0,0,0,0,1,100\n", "-7,-3,0,2\n",
+                  crate::input_for_day(7).expect("Can't find input")] {
+      let crabs = generator(input);
+      assert_eq!(part2_brute_force(&crabs), part2(&crabs));
+    }
+  }
+
+  #[test]
+  fn test_non_convex() {
+    // a flat cost for any move isn't convex, but the best is still found
+    let crabs = generator("0,10,10\n");
+    assert_eq!((10, 1), best_position(&crabs, |d| if d == 0 { 0 } else { 1 }));
+    // the fast paths agree with trying every position
+    let crabs = generator("3:4,0:2,9:5,4,7:0\n");
+    assert_eq!(min_total_cost(&crabs, |d| d as i64), part1(&crabs));
+    assert_eq!(min_total_cost(&crabs, cost), part2(&crabs));
+    let crabs = generator("5:0,8:0\n");
+    assert_eq!(0, part1(&crabs));
+    assert_eq!(0, part2(&crabs));
+  }
+
+  #[test]
+  fn test_cost_curves() {
+    let crabs = generator(INPUT);
+    assert_eq!(37, min_total_cost(&crabs, |d| d as i64));
+    assert_eq!(168, min_total_cost(&crabs, |d| (d as i64 + 1) * d as i64 / 2));
+    // squared distance is minimized at the mean, which is 4.9
+    assert_eq!(crabs.iter().map(|x| ((x.position - 5) * (x.position - 5)) as i64).sum::<i64>(),
+               min_total_cost(&crabs, |d| d as i64 * d as i64));
+  }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+/// The environment variable that holds the adventofcode.com session cookie.
+const SESSION_VAR: &str = "AOC_SESSION";
+
+/// The days that have puzzles.
+const DAYS: std::ops::RangeInclusive<usize> = 1..=25;
+
+/// Download the puzzle input for the given day into input/dayN.txt.
+/// An existing file is only replaced when force is set.
+pub fn fetch_input(day: usize, force: bool) -> Result<PathBuf, String> {
+  if !DAYS.contains(&day) {
+    return Err(format!("Day {} is outside of {}..={}", day, DAYS.start(), DAYS.end()));
+  }
+  let path = PathBuf::from(format!("input/day{}.txt", day));
+  if path.exists() && !force {
+    return Err(format!("{} already exists (use --force to overwrite)", path.display()));
+  }
+  let session = std::env::var(SESSION_VAR)
+    .map_err(|_| format!("{} must be set to your adventofcode.com session cookie",
+                         SESSION_VAR))?;
+  let url = format!("https://adventofcode.com/2021/day/{}/input", day);
+  let body = ureq::get(&url)
+    .header("Cookie", &format!("session={}", session))
+    .header("User-Agent", "github.com/omalley/advent_of_code_2021")
+    .call()
+    .and_then(|mut response| response.body_mut().read_to_string())
+    .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+  std::fs::write(&path, body)
+    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+  Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::fetch::fetch_input;
+
+  #[test]
+  fn test_bad_day() {
+    assert_eq!(Some(String::from("Day 0 is outside of 1..=25")), fetch_input(0, true).err());
+    assert_eq!(Some(String::from("Day 99 is outside of 1..=25")), fetch_input(99, true).err());
+  }
+}
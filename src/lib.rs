@@ -1,5 +1,6 @@
-use std::cmp::min;
+use std::any::Any;
 use std::fmt;
+use std::panic;
 use std::time;
 
 use colored::Colorize;
@@ -7,28 +8,31 @@ use colored::Colorize;
 /// Format the output of each line of the output.
 /// Includes the category, time, and result.
 fn pretty_print(f: &mut fmt::Formatter<'_>, line: &str,
-                duration: time::Duration,
-                output: Option<&str>) -> fmt::Result {
-    const DISPLAY_WIDTH: usize = 40;
-
-    let duration = format!("({:.2?})", duration);
+                timing: &Statistics,
+                output: Option<&Result<Answer, String>>,
+                display_width: usize) -> fmt::Result {
+    let duration = format!("({})", timing);
     write!(f, "{} {}", line, duration.dimmed())?;
 
     match output {
         Some(output) => {
             let width = "  - ".len() + line.chars().count() + 1 + duration.chars().count();
-            let dots = DISPLAY_WIDTH - min(DISPLAY_WIDTH - 5, width) - 2;
+            // always leave at least a few dots, even when the line is too wide
+            let dots = display_width.saturating_sub(width).max(5) - 2;
             write!(f, " {}", ".".repeat(dots).dimmed())?;
 
-            if output.contains('\n') {
-                writeln!(f)?;
+            match output {
+                Err(msg) => writeln!(f, " {}", format!("error: {}", msg).red()),
+                Ok(Answer::Grid(output)) => {
+                    writeln!(f)?;
 
-                for line in output.trim_matches('\n').lines() {
-                    writeln!(f, "    {}", line.bold())?;
-                }
-                Ok(())
-            } else {
-                writeln!(f, " {}", output.bold())
+                    for line in output.trim_matches('\n').lines() {
+                        writeln!(f, "    {}", line.bold())?;
+                    }
+                    Ok(())
+                },
+                Ok(Answer::Skipped) => writeln!(f, " {}", "skipped".dimmed()),
+                Ok(output) => writeln!(f, " {}", output.to_string().bold()),
             }
         },
         None => writeln!(f),
@@ -43,12 +47,160 @@ pub fn time<T>(func: &dyn Fn() -> T) -> (time::Duration, T) {
     (start.elapsed(), result)
 }
 
+/// Run the given function, converting a panic into an error message.
+pub fn catch<T>(func: &dyn Fn() -> T) -> Result<T, String> {
+    panic::catch_unwind(panic::AssertUnwindSafe(func))
+        .map_err(|e| panic_message(e.as_ref()))
+}
+
+/// Extract the message from a panic's payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+/// Summary of the times from running the same function several times.
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Statistics {
+    pub iters: usize,
+    pub min: time::Duration,
+    pub mean: time::Duration,
+    pub max: time::Duration,
+    pub stddev: time::Duration,
+}
+
+impl Statistics {
+    fn from_samples(samples: &[time::Duration]) -> Self {
+        let iters = samples.len();
+        let total: time::Duration = samples.iter().sum();
+        let mean = total / iters as u32;
+        let variance = samples.iter()
+            .map(|d| (d.as_secs_f64() - mean.as_secs_f64()).powi(2))
+            .sum::<f64>() / iters as f64;
+        Statistics{iters,
+                   min: *samples.iter().min().unwrap(),
+                   mean,
+                   max: *samples.iter().max().unwrap(),
+                   stddev: time::Duration::from_secs_f64(variance.sqrt())}
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2?}", self.mean)?;
+        if self.iters > 1 {
+            write!(f, " ± {:.2?}", self.stddev)?;
+        }
+        Ok(())
+    }
+}
+
+/// Time the given function over several iterations, returning the last
+/// result and the statistics of the elapsed times. Always runs at least once.
+pub fn time_n<T>(func: &dyn Fn() -> T, iters: usize) -> (Statistics, T) {
+    let (first, mut result) = time(func);
+    let mut samples = vec![first];
+    for _ in 1..iters {
+        let (elapsed, next) = time(func);
+        samples.push(elapsed);
+        result = next;
+    }
+    (Statistics::from_samples(&samples), result)
+}
+
+/// The options that control how each day is run.
+#[derive(Clone,Copy,Debug)]
+pub struct RunConfig {
+    /// the number of times to run each section for timing
+    pub repeat: usize,
+    /// the number of untimed runs of each part before timing it
+    pub warmup: usize,
+    /// the column to align the answers to when displaying the results
+    pub display_width: usize,
+    /// the only part to run (both parts by default)
+    pub part: Option<usize>,
+}
+
+impl RunConfig {
+    pub const DEFAULT_DISPLAY_WIDTH: usize = 40;
+
+    /// Should the given part (1 or 2) be run?
+    pub fn runs_part(&self, part: usize) -> bool {
+        self.part.is_none_or(|p| p == part)
+    }
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig{repeat: 1, warmup: 0, display_width: Self::DEFAULT_DISPLAY_WIDTH,
+                  part: None}
+    }
+}
+
+/// Time one of the parts as requested by the config, after first running
+/// it for the warm up iterations and discarding those results.
+pub fn time_part<T>(func: &dyn Fn() -> T, config: &RunConfig) -> (Statistics, T) {
+    for _ in 0..config.warmup {
+        func();
+    }
+    time_n(func, config.repeat)
+}
+
+/// The answer to a part, which keeps integers distinct from text such as
+/// the letters drawn in a grid.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Answer {
+    Integer(i128),
+    Text(String),
+    Grid(String),
+    /// the part wasn't run
+    Skipped,
+}
+
+macro_rules! integer_answer {
+    ( $($kind:ty),+ ) => {
+        $(impl From<$kind> for Answer {
+            fn from(val: $kind) -> Self {
+                Answer::Integer(val as i128)
+            }
+        })+
+    }
+}
+
+integer_answer!(i32, i64, u32, u64, usize);
+
+impl From<String> for Answer {
+    fn from(val: String) -> Self {
+        if val.contains('\n') {
+            Answer::Grid(val)
+        } else {
+            Answer::Text(val)
+        }
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Integer(val) => write!(f, "{}", val),
+            Answer::Text(val) | Answer::Grid(val) => write!(f, "{}", val),
+            Answer::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
 /// The times and results of running a day's code.
 pub struct DayResult {
     pub day: String,
-    generate_time: time::Duration,
-    part1: (time::Duration, String),
-    part2: (time::Duration, String),
+    generate_time: Statistics,
+    part1: (Statistics, Result<Answer, String>),
+    part2: (Statistics, Result<Answer, String>),
+    display_width: usize,
 }
 
 impl DayResult {
@@ -59,17 +211,87 @@ impl DayResult {
 
   /// Get the answers without the times
   pub fn get_answers(&self) -> Vec<String> {
-    vec![self.part1.1.to_string(), self.part2.1.to_string()]
+    [&self.part1.1, &self.part2.1].iter()
+      .map(|r| match r {
+        Ok(answer) => answer.to_string(),
+        Err(msg) => format!("error: {}", msg),
+      })
+      .collect()
+  }
+
+  /// Get the typed answer for part 1, or the error if it failed
+  pub fn part1_answer(&self) -> &Result<Answer, String> {
+    &self.part1.1
+  }
+
+  /// Get the typed answer for part 2, or the error if it failed
+  pub fn part2_answer(&self) -> &Result<Answer, String> {
+    &self.part2.1
+  }
+
+  /// Did both parts produce an answer?
+  pub fn is_ok(&self) -> bool {
+    self.part1.1.is_ok() && self.part2.1.is_ok()
+  }
+
+  /// Get the mean time spent in the generator
+  pub fn generate_time(&self) -> time::Duration {
+    self.generate_time.mean
+  }
+
+  /// Get the mean time spent in part 1
+  pub fn part1_time(&self) -> time::Duration {
+    self.part1.0.mean
+  }
+
+  /// Get the mean time spent in part 2
+  pub fn part2_time(&self) -> time::Duration {
+    self.part2.0.mean
+  }
+
+  /// Get the total mean time for the generator and both parts
+  pub fn total_time(&self) -> time::Duration {
+    self.generate_time() + self.part1_time() + self.part2_time()
+  }
+}
+
+impl DayResult {
+  /// Display the result along with its share of the batch's total runtime.
+  pub fn display_with_total(&self, total: time::Duration) -> DayResultWithTotal<'_> {
+    DayResultWithTotal{result: self, total}
+  }
+
+  fn write(&self, f: &mut fmt::Formatter<'_>,
+           total: Option<time::Duration>) -> fmt::Result {
+    let duration = format!("({:.2?})", self.total_time());
+    write!(f, "{} {}", self.pretty_day().bold(), duration.dimmed())?;
+    if let Some(total) = total {
+      let share = if total.is_zero() { 0.0 }
+        else { 100.0 * self.total_time().as_secs_f64() / total.as_secs_f64() };
+      write!(f, " {}", format!("({:.1}%)", share).dimmed())?;
+    }
+    writeln!(f)?;
+    pretty_print(f," · Generator", &self.generate_time, None, self.display_width)?;
+    pretty_print(f, " · Part 1", &self.part1.0, Some(&self.part1.1), self.display_width)?;
+    pretty_print(f, " · Part 2", &self.part2.0, Some(&self.part2.1), self.display_width)
   }
 }
 
 impl fmt::Display for DayResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let duration = format!("({:.2?})", self.generate_time + self.part1.0 + self.part2.0);
-        writeln!(f, "{} {}", self.pretty_day().bold(), duration.dimmed())?;
-        pretty_print(f," · Generator", self.generate_time, None)?;
-        pretty_print(f, " · Part 1", self.part1.0, Some(&self.part1.1))?;
-        pretty_print(f, " · Part 2", self.part2.0, Some(&self.part2.1))
+        self.write(f, None)
+    }
+}
+
+/// A day's result paired with the total runtime of the batch it ran in.
+pub struct DayResultWithTotal<'a> {
+    result: &'a DayResult,
+    total: time::Duration,
+}
+
+impl fmt::Display for DayResultWithTotal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.result.write(f, Some(self.total))
     }
 }
 
@@ -80,22 +302,40 @@ macro_rules! day_list {
         $(pub mod $day;)+
 
         /// Build a lambda to run each day's code
-        pub const FUNCS : &[&dyn Fn(&str) -> DayResult] = &[
-            $(&|data| {
-                let (generate_time, input) = time(&|| $day::generator(data));
-                let part1 = time(&|| $day::part1(&input));
-                let part2 = time(&|| $day::part2(&input));
+        pub const FUNCS : &[&(dyn Fn(&str, &RunConfig) -> DayResult + Sync)] = &[
+            $(&|data, config| {
+                let (generate_time, input) =
+                    time_n(&|| catch(&|| $day::generator(data)), config.repeat);
+                let (part1, part2) = match &input {
+                    Ok(input) => (
+                        if config.runs_part(1) {
+                            time_part(&|| catch(&|| Answer::from($day::part1(input))), config)
+                        } else {
+                            (Statistics::default(), Ok(Answer::Skipped))
+                        },
+                        if config.runs_part(2) {
+                            time_part(&|| catch(&|| Answer::from($day::part2(input))), config)
+                        } else {
+                            (Statistics::default(), Ok(Answer::Skipped))
+                        }),
+                    Err(msg) => {
+                        let err = Err(format!("generator failed: {}", msg));
+                        ((Statistics::default(), err.clone()), (Statistics::default(), err))
+                    },
+                };
                 DayResult{day: stringify!($day).to_string(),
                           generate_time,
-                          part1: (part1.0, part1.1.to_string()),
-                          part2: (part2.0, part2.1.to_string())}},)+
+                          part1,
+                          part2,
+                          display_width: config.display_width}},)+
         ];
 
         /// Define the list of implemented day names.
         pub const NAMES: &[&str] = &[$(stringify!($day)),*];
 
-        /// Load the inputs for each day into an array.
-        pub const INPUTS: &[&str] = &[$(include_str!(concat!("../input/", stringify!($day), ".txt"))),*];
+        /// Load the inputs for each day into an array. The build script copies
+        /// them into OUT_DIR and substitutes an empty input for missing files.
+        pub const INPUTS: &[&str] = &[$(include_str!(concat!(env!("OUT_DIR"), "/input/", stringify!($day), ".txt"))),*];
 }}
 
 day_list!(
@@ -124,4 +364,48 @@ day_list!(
         day23,
         day24,
         day25,
-    );
\ No newline at end of file
+    );
+
+/// Find the position in NAMES, INPUTS and FUNCS for the given name (eg. "day6").
+fn name_position(name: &str) -> Option<usize> {
+    NAMES.iter().position(|n| *n == name)
+}
+
+/// Find the position in NAMES, INPUTS and FUNCS for the given day number.
+fn day_position(day: usize) -> Option<usize> {
+    name_position(&format!("day{}", day))
+}
+
+/// Get the name of the given day number (eg. "day6"), if it is implemented.
+pub fn name_for_day(day: usize) -> Option<&'static str> {
+    day_position(day).map(|p| NAMES[p])
+}
+
+/// Get the input for the given day number, if it is implemented.
+pub fn input_for_day(day: usize) -> Option<&'static str> {
+    day_position(day).map(|p| INPUTS[p])
+}
+
+/// Run the named day (eg. "day6") on the given input with the default config.
+/// Returns None if the day isn't implemented.
+pub fn run_day(name: &str, input: &str) -> Option<DayResult> {
+    name_position(name).map(|p| FUNCS[p](input, &RunConfig::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{input_for_day, name_for_day, run_day, Answer};
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(Some("day6"), name_for_day(6));
+        assert_eq!(None, name_for_day(99));
+        assert_eq!(None, input_for_day(99));
+        let result = run_day("day1", input_for_day(1).expect("Can't find input"))
+            .expect("Can't find day1");
+        assert_eq!("day1", result.day);
+        assert_eq!(Ok(Answer::Integer(1688)), result.part1.1);
+        assert_eq!(Ok(Answer::Integer(1728)), result.part2.1);
+        assert!(run_day("day99", "").is_none());
+    }
+}
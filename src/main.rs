@@ -1,15 +1,108 @@
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path,PathBuf};
+use std::time::{Duration,SystemTime,UNIX_EPOCH};
 use argh::FromArgs;
 use colored::Colorize;
-use omalley_aoc2021::{DayResult,FUNCS,INPUTS,NAMES,time};
+use itertools::{EitherOrBoth,Itertools};
+use omalley_aoc2021::{Answer,DayResult,FUNCS,INPUTS,NAMES,RunConfig,name_for_day,time};
 use serde::{Deserialize,Serialize};
+use strum_macros::EnumString;
+
+mod fetch;
+
+/// The supported styles for printing the results.
+#[derive(Clone,Copy,Debug,EnumString,PartialEq)]
+#[strum(serialize_all = "lowercase")]
+enum Format {
+  Text,
+  Markdown,
+}
+
+/// The supported orders for printing the results.
+#[derive(Clone,Copy,Debug,EnumString,PartialEq)]
+#[strum(serialize_all = "lowercase")]
+enum SortOrder {
+  Day,
+  Time,
+}
 
 #[derive(FromArgs)]
 /** Solution for Advent of Code (https://adventofcode.com/)*/
 struct Args {
-  /// A single day to execute (all days by default)
+  /// a single day to execute (all days by default)
   #[argh(option, short = 'd')]
   day: Option<usize>,
+
+  /// the output format: text or markdown
+  #[argh(option, default = "Format::Text")]
+  format: Format,
+
+  /// the order to print the days: day or time (slowest first)
+  #[argh(option, default = "SortOrder::Day")]
+  sort: SortOrder,
+
+  /// print only the answers, one per line
+  #[argh(switch, short = 'q')]
+  quiet: bool,
+
+  /// print a bar chart of each day's runtime
+  #[argh(switch)]
+  bars: bool,
+
+  /// the number of times to run each part for timing
+  #[argh(option, default = "1")]
+  repeat: usize,
+
+  /// run only the given part (1 or 2)
+  #[argh(option, from_str_fn(parse_part))]
+  part: Option<usize>,
+
+  /// the number of untimed runs of each part before timing it
+  #[argh(option, default = "0")]
+  warmup: usize,
+
+  /// the column to align the answers to
+  #[argh(option, default = "RunConfig::DEFAULT_DISPLAY_WIDTH")]
+  width: usize,
+
+  /// compare against the stored answers instead of updating them
+  #[argh(switch)]
+  verify: bool,
+
+  /// disable colored output
+  #[argh(switch)]
+  no_color: bool,
+
+  /// list the implemented days and their stored answers
+  #[argh(switch)]
+  list: bool,
+
+  /// download the input for the given day from adventofcode.com
+  #[argh(option)]
+  fetch: Option<usize>,
+
+  /// the file to store the answers in
+  #[argh(option, default = "PathBuf::from(Answers::FILENAME)")]
+  answers: PathBuf,
+
+  /// append the timings from this run to the given file
+  #[argh(option)]
+  timing_log: Option<PathBuf>,
+
+  /// allow --fetch to overwrite an existing input file
+  #[argh(switch)]
+  force: bool,
+}
+
+/// Parse the --part option, which must be 1 or 2.
+fn parse_part(value: &str) -> Result<usize, String> {
+  match value {
+    "1" => Ok(1),
+    "2" => Ok(2),
+    _ => Err(String::from("part must be 1 or 2")),
+  }
 }
 
 #[derive(Default,Deserialize,Serialize)]
@@ -21,63 +114,289 @@ struct Answers {
 impl Answers {
   const FILENAME: &'static str = "answers.yml";
 
-  fn read() -> Self {
-    if let Ok(f) = std::fs::File::open(Self::FILENAME) {
+  fn read(path: &Path) -> Self {
+    if let Ok(f) = std::fs::File::open(path) {
       serde_yaml::from_reader(f).expect("Could not read answers")
     } else {
       Self::default()
     }
   }
 
-  fn update(&mut self, delta_list: &Vec<DayResult>) {
-    for delta in delta_list {
-      let new_val = delta.get_answers();
+  fn update(&mut self, delta_list: &[DayResult]) {
+    for delta in delta_list.iter().filter(|d| d.is_ok()) {
+      let Some(new_val) = self.merged_answers(delta) else {
+        continue;
+      };
       if let Some(prev) =
           self.days.insert(delta.day.to_string(), new_val.clone()) {
         if prev != new_val {
-          println!("{}", format!("Output for {} changed from {:?} to {:?}!",
-                                 delta.pretty_day(), prev, new_val).bold());
+          if is_multi_line(&prev) || is_multi_line(&new_val) {
+            println!("{}", format!("Output for {} changed:", delta.pretty_day()).bold());
+            print_diff(&prev, &new_val);
+          } else {
+            println!("{}", format!("Output for {} changed from {:?} to {:?}!",
+                                   delta.pretty_day(), prev, new_val).bold());
+          }
         }
       }
     }
   }
 
-  fn write(&self) {
+  /// Get the day's answers with any skipped parts filled in from the stored
+  /// answers. Returns None if a skipped part has no stored answer.
+  fn merged_answers(&self, result: &DayResult) -> Option<Vec<String>> {
+    let prev = self.days.get(&result.day);
+    [result.part1_answer(), result.part2_answer()].iter().enumerate()
+      .map(|(i, answer)| match answer {
+        Ok(Answer::Skipped) => prev.map(|p| p[i].clone()),
+        _ => Some(result.get_answers()[i].clone()),
+      })
+      .collect()
+  }
+
+  /// Compare the results to the stored answers without changing them.
+  /// Returns the days whose answers differ. Days without a stored answer
+  /// are reported as new, but aren't failures.
+  fn verify(&self, results: &[DayResult]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for r in results {
+      match (self.days.get(&r.day), self.merged_answers(r)) {
+        (None, _) | (_, None) => println!("{} is new", r.pretty_day()),
+        (Some(prev), Some(new_val)) if *prev != new_val => {
+          println!("{}", format!("Output for {} differs: expected {:?} but got {:?}!",
+                                 r.pretty_day(), prev, new_val).bold());
+          failures.push(r.pretty_day());
+        }
+        _ => {}
+      }
+    }
+    failures
+  }
+
+  fn write(&self, path: &Path) {
     let f = std::fs::OpenOptions::new()
       .write(true)
       .create(true)
       .truncate(true)
-      .open(Self::FILENAME)
+      .open(path)
       .expect("Couldn't open file");
     serde_yaml::to_writer(f, self).unwrap();
   }
 }
 
+/// Do any of the answers span several lines?
+fn is_multi_line(answers: &[String]) -> bool {
+  answers.iter().any(|a| a.contains('\n'))
+}
+
+/// Print a line by line diff of each part's answers with the old lines
+/// in red and the new lines in green.
+fn print_diff(prev: &[String], new_val: &[String]) {
+  for (part, pair) in prev.iter().zip_longest(new_val.iter()).enumerate() {
+    let (old, new) = match pair {
+      EitherOrBoth::Both(old, new) => (old.as_str(), new.as_str()),
+      EitherOrBoth::Left(old) => (old.as_str(), ""),
+      EitherOrBoth::Right(new) => ("", new.as_str()),
+    };
+    if old == new {
+      continue;
+    }
+    println!("  Part {}:", part + 1);
+    for line in old.lines().zip_longest(new.lines()) {
+      match line {
+        EitherOrBoth::Both(o, n) if o == n => println!("    {}", o),
+        EitherOrBoth::Both(o, n) => {
+          println!("  {}", format!("- {}", o).red());
+          println!("  {}", format!("+ {}", n).green());
+        }
+        EitherOrBoth::Left(o) => println!("  {}", format!("- {}", o).red()),
+        EitherOrBoth::Right(n) => println!("  {}", format!("+ {}", n).green()),
+      }
+    }
+  }
+}
+
+/// Render an answer so that it fits in a single markdown table cell.
+fn markdown_cell(answer: &str) -> String {
+  if answer.contains('\n') {
+    String::from("(grid)")
+  } else {
+    answer.replace('|', "\\|")
+  }
+}
+
+/// Print the results as a markdown table.
+fn print_markdown(results: &[DayResult]) {
+  println!("| Day | Part 1 | Part 2 | Total time |");
+  println!("| --- | --- | --- | ---: |");
+  for r in results {
+    let answers = r.get_answers();
+    println!("| {} | {} | {} | {:.2?} |", r.pretty_day(), markdown_cell(&answers[0]),
+             markdown_cell(&answers[1]), r.total_time());
+  }
+}
+
+/// Append the timings to the log with one tab separated line per day:
+/// the unix timestamp, the day, and the generator, part 1, and part 2 times
+/// in nanoseconds.
+fn append_timing_log(path: &Path, results: &[DayResult]) -> std::io::Result<()> {
+  let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  let mut f = std::fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)?;
+  for r in results {
+    writeln!(f, "{}\t{}\t{}\t{}\t{}", timestamp, r.day, r.generate_time().as_nanos(),
+             r.part1_time().as_nanos(), r.part2_time().as_nanos())?;
+  }
+  Ok(())
+}
+
+/// Print the slowest and fastest days, or just the total for a single day.
+fn print_extremes(results: &[DayResult]) {
+  let slowest = results.iter().max_by_key(|r| r.total_time());
+  let fastest = results.iter().min_by_key(|r| r.total_time());
+  match (slowest, fastest) {
+    (Some(only), _) if results.len() == 1 =>
+      println!("Total: {} ({:.2?})", only.pretty_day(), only.total_time()),
+    (Some(slowest), Some(fastest)) =>
+      println!("Slowest: {} ({:.2?}), Fastest: {} ({:.2?})",
+               slowest.pretty_day(), slowest.total_time(),
+               fastest.pretty_day(), fastest.total_time()),
+    _ => {}
+  }
+}
+
+/// Print a bar chart of each day's runtime scaled to the slowest day along
+/// with its share of the overall runtime. Without color, only the numbers
+/// are printed.
+fn print_bars(results: &[DayResult], elapsed: Duration) {
+  const BAR_WIDTH: usize = 40;
+  let slowest = results.iter().map(|r| r.total_time()).max().unwrap_or_default();
+  let draw_bars = colored::control::SHOULD_COLORIZE.should_colorize();
+  for r in results {
+    let share = if elapsed.is_zero() { 0.0 }
+      else { 100.0 * r.total_time().as_secs_f64() / elapsed.as_secs_f64() };
+    let numbers = format!("{:.2?} ({:.1}%)", r.total_time(), share);
+    if draw_bars {
+      let length = if slowest.is_zero() { 0 } else {
+        (BAR_WIDTH as f64 * r.total_time().as_secs_f64() / slowest.as_secs_f64()).round() as usize
+      };
+      let bar = format!("{:<width$}", "█".repeat(length), width = BAR_WIDTH);
+      println!("{:>6} {} {}", r.pretty_day(), bar.cyan(), numbers.dimmed());
+    } else {
+      println!("{:>6} {}", r.pretty_day(), numbers);
+    }
+  }
+}
+
+/// Print each implemented day along with any stored answers.
+fn print_list(answers: &Answers) {
+  for name in NAMES {
+    let pretty = name.replace("day", "Day ");
+    match answers.days.get(*name) {
+      Some(stored) => {
+        let stored: Vec<&str> = stored.iter()
+          .map(|a| if a.contains('\n') { "(grid)" } else { a })
+          .collect();
+        println!("{}: {}", pretty.bold(), stored.join(", "));
+      }
+      None => println!("{}: {}", pretty.bold(), "no stored answer".dimmed()),
+    }
+  }
+}
+
 fn main() {
     let args: Args = argh::from_env();
+    // Follow the NO_COLOR convention (https://no-color.org/)
+    if args.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+      colored::control::set_override(false);
+    }
+    if let Some(day) = args.fetch {
+      match fetch::fetch_input(day, args.force) {
+        Ok(path) => println!("Wrote {}", path.display()),
+        Err(msg) => {
+          eprintln!("{}", msg);
+          std::process::exit(1);
+        }
+      }
+      return;
+    }
+    if args.list {
+      print_list(&Answers::read(&args.answers));
+      return;
+    }
     // Did the user pick a single day to run
-    let day_filter: Option<usize> = match args.day {
-        Some(day) => {
-            let name = format!("day{}", day);
-            Some(NAMES.iter().position(|x| **x == name)
-              .expect("Requested an unimplemented day"))
-        },
-        None => None
-    };
+    let day_filter: Option<&str> = args.day
+      .map(|day| name_for_day(day).expect("Requested an unimplemented day"));
 
-     let (elapsed, results) = time(&|| {
-        crate::FUNCS.iter().enumerate()
-          .filter(|(p, _)| day_filter.is_none() || day_filter.unwrap() == *p)
-          .map(|(p, f)| f(INPUTS[p]))
+    let config = RunConfig{repeat: args.repeat, warmup: args.warmup,
+                           display_width: args.width, part: args.part};
+
+    // Run each selected day on its own thread and join them back in day order.
+    let (elapsed, mut results) = time(&|| {
+      std::thread::scope(|scope| {
+        let handles: Vec<_> = crate::FUNCS.iter().enumerate()
+          .filter(|(p, _)| day_filter.is_none_or(|name| name == NAMES[*p]))
+          .map(|(p, f)| scope.spawn(move || f(INPUTS[p], &config)))
+          .collect();
+        handles.into_iter()
+          .map(|h| h.join().expect("Day panicked"))
           .collect::<Vec<DayResult>>()
+      })
     });
 
-    for r in &results {
-      println!("{}", r);
+    if args.sort == SortOrder::Time {
+      results.sort_by_key(|r| Reverse(r.total_time()));
+    }
+
+    if args.quiet {
+      for answer in results.iter().flat_map(|r| r.get_answers()) {
+        println!("{}", answer);
+      }
+    } else {
+      match args.format {
+        Format::Text => {
+          for r in &results {
+            println!("{}", r.display_with_total(elapsed));
+          }
+          println!("{} {}", "Overall runtime".bold(), format!("({:.2?})", elapsed).dimmed());
+          print_extremes(&results);
+          if args.bars {
+            print_bars(&results, elapsed);
+          }
+        }
+        Format::Markdown => print_markdown(&results),
+      }
+    }
+
+    if let Some(path) = &args.timing_log {
+      if let Err(e) = append_timing_log(path, &results) {
+        eprintln!("Couldn't write timing log {}: {}", path.display(), e);
+      }
+    }
+
+    let mut old_answers = Answers::read(&args.answers);
+    if args.verify {
+      let failures = old_answers.verify(&results);
+      if !failures.is_empty() {
+        eprintln!("Answers changed for: {}", failures.join(", "));
+        std::process::exit(1);
+      }
+    } else {
+      old_answers.update(&results);
+      old_answers.write(&args.answers);
     }
-    println!("{} {}", "Overall runtime".bold(), format!("({:.2?})", elapsed).dimmed());
 
-    let mut old_answers = Answers::read();
-    old_answers.update(&results);
-    old_answers.write();
+    // Let wrapping scripts know if any of the days failed
+    let failed: Vec<String> = results.iter()
+      .filter(|r| !r.is_ok())
+      .map(|r| r.pretty_day())
+      .collect();
+    if !failed.is_empty() {
+      eprintln!("Failed: {}", failed.join(", "));
+      std::process::exit(1);
+    }
 }